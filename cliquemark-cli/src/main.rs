@@ -0,0 +1,488 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{Parser, ValueEnum};
+
+use cliquemark_core::{
+    apply_watermark,
+    collect_image_files,
+    create_target_folder,
+    load_image,
+    target_paths,
+    Anchor,
+    BatchControl,
+    BlendMode,
+    FontSource,
+    Geometry,
+    NameTemplate,
+    OutputFormat,
+    OutputSettings,
+    PngCompression,
+    Progress,
+    Reframe,
+    Resize,
+    TextWatermark,
+    Tiling,
+    WatermarkError,
+    WatermarkJob,
+    WatermarkSettings,
+    WatermarkSource,
+    TARGET_FOLDER_NAME,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AnchorArg {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl From<AnchorArg> for Anchor {
+    fn from(anchor: AnchorArg) -> Anchor {
+        match anchor {
+            AnchorArg::TopLeft => Anchor::TopLeft,
+            AnchorArg::TopCenter => Anchor::TopCenter,
+            AnchorArg::TopRight => Anchor::TopRight,
+            AnchorArg::CenterLeft => Anchor::CenterLeft,
+            AnchorArg::Center => Anchor::Center,
+            AnchorArg::CenterRight => Anchor::CenterRight,
+            AnchorArg::BottomLeft => Anchor::BottomLeft,
+            AnchorArg::BottomCenter => Anchor::BottomCenter,
+            AnchorArg::BottomRight => Anchor::BottomRight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BlendArg {
+    Normal,
+    Screen,
+    Multiply,
+    Overlay,
+    SoftLight,
+    Difference,
+}
+
+impl From<BlendArg> for BlendMode {
+    fn from(blend: BlendArg) -> BlendMode {
+        match blend {
+            BlendArg::Normal => BlendMode::Normal,
+            BlendArg::Screen => BlendMode::Screen,
+            BlendArg::Multiply => BlendMode::Multiply,
+            BlendArg::Overlay => BlendMode::Overlay,
+            BlendArg::SoftLight => BlendMode::SoftLight,
+            BlendArg::Difference => BlendMode::Difference,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Keep,
+    Jpeg,
+    Png,
+    Webp,
+    Avif,
+}
+
+impl From<FormatArg> for OutputFormat {
+    fn from(format: FormatArg) -> OutputFormat {
+        match format {
+            FormatArg::Keep => OutputFormat::Keep,
+            FormatArg::Jpeg => OutputFormat::Jpeg,
+            FormatArg::Png => OutputFormat::Png,
+            FormatArg::Webp => OutputFormat::WebP,
+            FormatArg::Avif => OutputFormat::Avif,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PngCompressionArg {
+    Fast,
+    Balanced,
+    Smallest,
+}
+
+impl From<PngCompressionArg> for PngCompression {
+    fn from(compression: PngCompressionArg) -> PngCompression {
+        match compression {
+            PngCompressionArg::Fast => PngCompression::Fast,
+            PngCompressionArg::Balanced => PngCompression::Balanced,
+            PngCompressionArg::Smallest => PngCompression::Smallest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Offset {
+    Pixels(f64),
+    Percent(f64),
+}
+
+impl Offset {
+    // as a fraction of `length`, the image width or height the offset runs along
+    fn relative(self, length: f64) -> f32 {
+        match self {
+            Offset::Pixels(pixels) => (pixels / length) as f32,
+            Offset::Percent(percent) => (percent / 100.0) as f32,
+        }
+    }
+}
+
+fn parse_offset(offset: &str) -> Result<Offset, String> {
+    match offset.strip_suffix('%') {
+        Some(percent) => percent.trim().parse().map(Offset::Percent),
+        None => offset.trim_end_matches("px").trim().parse().map(Offset::Pixels),
+    }
+    .map_err(|_| "expected pixels like -20 or a percentage like 2.5%".to_string())
+}
+
+// "WIDTHxHEIGHT" for exact sizes, "WIDTH:HEIGHT" for aspect ratios
+fn parse_pair(pair: &str, separator: char) -> Result<[u32; 2], String> {
+    let error = || format!("expected two whole numbers separated by '{}'", separator);
+    let (first, second) = pair.split_once(separator).ok_or_else(error)?;
+
+    let pair = [first, second].map(|number| number.trim().parse::<u32>().ok().filter(|number| *number > 0));
+    match pair {
+        [Some(first), Some(second)] => Ok([first, second]),
+        _ => Err(error()),
+    }
+}
+
+fn parse_name_template(template: &str) -> Result<NameTemplate, String> {
+    NameTemplate::parse(template).map_err(|error| error.to_string())
+}
+
+fn parse_size(size: &str) -> Result<[u32; 2], String> {
+    parse_pair(&size.to_lowercase(), 'x')
+}
+
+fn parse_aspect_ratio(aspect_ratio: &str) -> Result<[u32; 2], String> {
+    parse_pair(aspect_ratio, ':')
+}
+
+/// Watermark every image in a folder without opening the Cliquemark window.
+///
+/// Scale and margin are measured on the first readable image of the folder, the same way the
+/// app measures them on its preview image, and applied proportionally to the others.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Folder with the images to watermark
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Also watermark the images in sub-folders, the output mirrors the folder structure
+    #[arg(short = 'R', long)]
+    recursive: bool,
+
+    /// Scale images down to at most this width in pixels before watermarking
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_width: Option<u32>,
+
+    /// Scale images down to at most this height in pixels before watermarking
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_height: Option<u32>,
+
+    /// Resize images to exactly WIDTHxHEIGHT pixels before watermarking, e.g. 1080x1350,
+    /// combine with --crop to the same ratio to avoid stretching
+    #[arg(long, value_parser = parse_size, conflicts_with_all = ["max_width", "max_height"])]
+    size: Option<[u32; 2]>,
+
+    /// Crop images to an aspect ratio like 4:5 before resizing and watermarking
+    #[arg(long, value_parser = parse_aspect_ratio, conflicts_with = "pad")]
+    crop: Option<[u32; 2]>,
+
+    /// Part of the image kept when cropping
+    #[arg(long, value_enum, default_value_t = AnchorArg::Center, requires = "crop")]
+    gravity: AnchorArg,
+
+    /// Pad images to an aspect ratio like 1:1 before resizing and watermarking, keeping them centred
+    #[arg(long, value_parser = parse_aspect_ratio)]
+    pad: Option<[u32; 2]>,
+
+    /// Colour of the padding as #RRGGBB or #RRGGBBAA
+    #[arg(long, default_value = "#FFFFFF", value_parser = parse_color, requires = "pad")]
+    pad_color: [u8; 4],
+
+    /// Image to use as watermark
+    #[arg(short, long, required_unless_present = "text", conflicts_with = "text")]
+    watermark: Option<PathBuf>,
+
+    /// Text to use as watermark instead of an image, e.g. "© Name 2026"
+    #[arg(short, long)]
+    text: Option<String>,
+
+    /// Font family of an installed font or path to a TTF/OTF file, for text watermarks
+    #[arg(long, default_value = "Sans", requires = "text")]
+    font: String,
+
+    /// Text colour as #RRGGBB or #RRGGBBAA, for text watermarks
+    #[arg(long, default_value = "#FFFFFF", value_parser = parse_color, requires = "text")]
+    color: [u8; 4],
+
+    /// Font weight from 100 (thin) to 900 (black), for text watermarks
+    #[arg(long, default_value_t = 400, value_parser = clap::value_parser!(u16).range(100..=900), requires = "text")]
+    weight: u16,
+
+    /// Point of the image the watermark is anchored to
+    #[arg(short, long, visible_alias = "corner", value_enum, default_value_t = AnchorArg::BottomRight)]
+    anchor: AnchorArg,
+
+    /// Horizontal shift after anchoring, in pixels or with a % suffix in percent of the image width,
+    /// positive moves right
+    #[arg(long, default_value = "0", value_parser = parse_offset, allow_hyphen_values = true)]
+    offset_x: Offset,
+
+    /// Vertical shift after anchoring, in pixels or with a % suffix in percent of the image height,
+    /// positive moves down
+    #[arg(long, default_value = "0", value_parser = parse_offset, allow_hyphen_values = true)]
+    offset_y: Offset,
+
+    /// Clockwise rotation of the watermark in degrees, the anchor places the box around the rotated watermark
+    #[arg(short, long, default_value_t = 0.0, allow_hyphen_values = true)]
+    rotation: f32,
+
+    /// Fraction of the image's short side covered by the watermark
+    #[arg(short, long, default_value_t = 0.2)]
+    scale: f64,
+
+    /// Margin between the watermark and the image edges, in pixels
+    #[arg(short, long, default_value_t = 0.0)]
+    margin: f64,
+
+    /// Repeat the watermark over the whole image instead of anchoring it,
+    /// the margin and offsets then move the first tile
+    #[arg(long)]
+    tile: bool,
+
+    /// Horizontal gap between tiles, in pixels
+    #[arg(long, default_value_t = 0.0, requires = "tile")]
+    tile_spacing_x: f64,
+
+    /// Vertical gap between tiles, in pixels
+    #[arg(long, default_value_t = 0.0, requires = "tile")]
+    tile_spacing_y: f64,
+
+    /// Shift of every other row of tiles in percent of a tile step, 50 gives a brick pattern
+    #[arg(long, default_value_t = 0.0, requires = "tile")]
+    row_offset: f64,
+
+    /// Opacity of the watermark in percent
+    #[arg(short = 'p', long, default_value_t = 100.0)]
+    opacity: f64,
+
+    /// How the watermark's colours mix with the image, e.g. screen keeps dark logos visible on dark photos
+    #[arg(short, long, value_enum, default_value_t = BlendArg::Normal)]
+    blend: BlendArg,
+
+    /// Output folder, defaults to a new `watermarked` folder inside the input folder
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Template for the output names instead of the original ones, e.g. "client_{date}_{counter:04}",
+    /// a '/' creates sub-folders and the extension is added automatically.
+    /// Tokens: {stem} {ext} {counter} {counter:04} {date} {time} {year} {month} {day} {camera} {width} {height} {folder}
+    #[arg(short, long, value_parser = parse_name_template)]
+    name: Option<NameTemplate>,
+
+    /// Format of the watermarked images
+    #[arg(short, long, value_enum, default_value_t = FormatArg::Keep)]
+    format: FormatArg,
+
+    /// Encoder quality from 1 to 100, for JPEG, AVIF and lossy WebP
+    #[arg(short, long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+
+    /// Compression effort for PNG
+    #[arg(long, value_enum, default_value_t = PngCompressionArg::Fast)]
+    png_compression: PngCompressionArg,
+
+    /// Write WebPs lossy with --quality instead of lossless
+    #[arg(long)]
+    lossy_webp: bool,
+
+    /// Colour as #RRGGBB that transparent images are flattened onto when written as JPEG
+    #[arg(long, default_value = "#FFFFFF", value_parser = parse_opaque_color)]
+    background: [u8; 3],
+}
+
+fn parse_color(color: &str) -> Result<[u8; 4], String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return Err("expected #RRGGBB or #RRGGBBAA".to_string());
+    }
+
+    let mut rgba = [255; 4];
+    for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|error| error.to_string())?;
+    }
+    Ok(rgba)
+}
+
+// for colours that are always opaque, where an alpha channel would silently be dropped
+fn parse_opaque_color(color: &str) -> Result<[u8; 3], String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 {
+        return Err("expected #RRGGBB, the background can't be transparent".to_string());
+    }
+
+    parse_color(color).map(|[red, green, blue, _]| [red, green, blue])
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// returns whether every image was watermarked
+fn run(args: &Args) -> Result<bool, Box<dyn std::error::Error>> {
+    if !(0.0..=1.0).contains(&args.scale) {
+        return Err("scale must be between 0 and 1".into());
+    }
+    if !(0.0..=100.0).contains(&args.opacity) {
+        return Err("opacity must be between 0 and 100".into());
+    }
+
+    let image_entries = collect_image_files(&args.input, args.recursive)?;
+    let watermark = match (&args.text, &args.watermark) {
+        (Some(text), _) => {
+            let font_path = PathBuf::from(&args.font);
+            let font = if font_path.is_file() { FontSource::File(font_path) } else { FontSource::System(args.font.clone()) };
+
+            let text_watermark = TextWatermark {
+                text: text.clone(),
+                font,
+                color: args.color,
+                weight: args.weight,
+            };
+            WatermarkSource::Text(text_watermark.renderer()?)
+        }
+        (None, Some(watermark)) => WatermarkSource::Image(load_image(watermark)?),
+        (None, None) => return Err("either --watermark or --text is required".into()),
+    };
+
+    let reframe = match (args.crop, args.pad) {
+        (Some(aspect_ratio), _) => Some(Reframe::Crop { aspect_ratio, gravity: args.gravity.into() }),
+        (None, Some(aspect_ratio)) => Some(Reframe::Pad { aspect_ratio, color: args.pad_color }),
+        (None, None) => None,
+    };
+    let resize = match (args.size, args.max_width, args.max_height) {
+        (Some([width, height]), _, _) => Some(Resize::Exact { width, height }),
+        (None, None, None) => None,
+        (None, max_width, max_height) => Some(Resize::Fit { max_width, max_height }),
+    };
+    let geometry = Geometry { reframe, resize };
+
+    // measured after the geometry, like every image of the batch
+    let reference_image = image_entries
+        .iter()
+        .find_map(|image_entry| load_image(image_entry).ok())
+        .map(|image| geometry.apply(image))
+        .ok_or("none of the input images could be decoded")?;
+
+    let reference_width = reference_image.width() as f64;
+    let tiling = args.tile.then(|| Tiling {
+        horizontal_spacing: (args.tile_spacing_x / reference_width) as f32,
+        vertical_spacing: (args.tile_spacing_y / reference_width) as f32,
+        row_offset: (args.row_offset / 100.0) as f32,
+    });
+
+    let relative_offset = [
+        args.offset_x.relative(reference_width),
+        args.offset_y.relative(reference_image.height() as f64),
+    ];
+
+    let settings = WatermarkSettings {
+        relative_offset,
+        rotation: args.rotation,
+        tiling,
+        opacity: (args.opacity / 100.0) as f32,
+        blend_mode: args.blend.into(),
+        ..WatermarkSettings::from_scale(
+            args.scale,
+            args.margin,
+            [reference_image.width(), reference_image.height()],
+            watermark.dimensions(),
+            args.anchor.into(),
+        )
+    };
+
+    let target_folder = match &args.output {
+        Some(output) => {
+            fs::create_dir_all(output)?;
+            output.clone()
+        }
+        None => create_target_folder(TARGET_FOLDER_NAME, &args.input)?,
+    };
+
+    let watermark_job = WatermarkJob {
+        image_entries,
+        source_folder: args.input.clone(),
+        watermark,
+        target_folder,
+        geometry,
+        settings,
+        output: OutputSettings {
+            format: args.format.into(),
+            quality: args.quality,
+            png_compression: args.png_compression.into(),
+            webp_lossless: !args.lossy_webp,
+            background: args.background,
+        },
+        name_template: args.name.clone(),
+    };
+
+    // nothing is written when images would overwrite each other's output
+    let target_paths = target_paths(&watermark_job);
+    let collisions = target_paths
+        .iter()
+        .filter_map(|target_path| target_path.as_ref().err().filter(|error| matches!(error, WatermarkError::NameCollision { .. })))
+        .collect::<Vec<_>>();
+    if !collisions.is_empty() {
+        for collision in &collisions {
+            eprintln!("{}", collision);
+        }
+        if args.output.is_none() {
+            let _ = fs::remove_dir(&watermark_job.target_folder);
+        }
+        return Err(format!("{} output names collide, tell them apart with {{counter}} or {{ext}} in --name", collisions.len()).into());
+    }
+
+    let results = apply_watermark(&watermark_job, target_paths, &BatchControl::default(), |progress| {
+        if let Progress::Processed { completed, total, result } = progress {
+            match (&result.result, &result.target) {
+                (Ok(()), Some(target)) => println!("[{}/{}] {} -> {}", completed, total, result.source.display(), target.display()),
+                (Ok(()), None) => println!("[{}/{}] {}", completed, total, result.source.display()),
+                (Err(error), _) => eprintln!("[{}/{}] {} failed: {}", completed, total, result.source.display(), error),
+            }
+        }
+    });
+
+    let failed = results.iter().filter(|result| result.result.is_err()).count();
+    println!(
+        "{} of {} images watermarked into {}",
+        results.len() - failed,
+        results.len(),
+        watermark_job.target_folder.display(),
+    );
+    Ok(failed == 0)
+}
@@ -0,0 +1,162 @@
+use image::{
+    imageops,
+    DynamicImage,
+    GenericImage,
+    GenericImageView,
+    Rgba,
+    RgbaImage,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Screen,
+    Multiply,
+    Overlay,
+    SoftLight,
+    Difference,
+}
+
+impl BlendMode {
+    // the same order as the blend mode dropdown in the settings sidebar
+    pub const ALL: [BlendMode; 6] = [
+        BlendMode::Normal,
+        BlendMode::Screen,
+        BlendMode::Multiply,
+        BlendMode::Overlay,
+        BlendMode::SoftLight,
+        BlendMode::Difference,
+    ];
+
+    pub fn from_index(index: u32) -> BlendMode {
+        BlendMode::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        BlendMode::ALL.iter().position(|blend_mode| *blend_mode == self).unwrap_or_default() as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Screen => "Screen",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Overlay => "Overlay",
+            BlendMode::SoftLight => "Soft light",
+            BlendMode::Difference => "Difference",
+        }
+    }
+
+    // blended colour channel of the watermark over the image, both channels in 0.0..=1.0,
+    // formulas of the W3C compositing spec
+    fn blend_channel(self, backdrop: f32, source: f32) -> f32 {
+        let screen = |backdrop: f32, source: f32| backdrop + source - backdrop * source;
+
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Screen => screen(backdrop, source),
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Overlay => {
+                if backdrop <= 0.5 {
+                    2.0 * backdrop * source
+                } else {
+                    screen(2.0 * backdrop - 1.0, source)
+                }
+            }
+            BlendMode::SoftLight => {
+                if source <= 0.5 {
+                    backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+                } else {
+                    let darkened = if backdrop <= 0.25 {
+                        ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                    } else {
+                        backdrop.sqrt()
+                    };
+                    backdrop + (2.0 * source - 1.0) * (darkened - backdrop)
+                }
+            }
+            BlendMode::Difference => (backdrop - source).abs(),
+        }
+    }
+}
+
+// composites `watermark` onto `image` with its top left corner at `[x, y]`,
+// parts outside the image are skipped like with `imageops::overlay`
+pub fn blend_overlay(image: &mut DynamicImage, watermark: &RgbaImage, x: i64, y: i64, blend_mode: BlendMode) {
+    if blend_mode == BlendMode::Normal {
+        imageops::overlay(image, watermark, x, y);
+        return;
+    }
+
+    let (image_width, image_height) = (image.width() as i64, image.height() as i64);
+
+    for (watermark_x, watermark_y, watermark_pixel) in watermark.enumerate_pixels() {
+        let (pixel_x, pixel_y) = (x + watermark_x as i64, y + watermark_y as i64);
+        if watermark_pixel[3] == 0 || pixel_x < 0 || pixel_y < 0 || pixel_x >= image_width || pixel_y >= image_height {
+            continue;
+        }
+
+        let image_pixel = image.get_pixel(pixel_x as u32, pixel_y as u32);
+        let source_alpha = watermark_pixel[3] as f32 / 255.0;
+        let backdrop_alpha = image_pixel[3] as f32 / 255.0;
+        let alpha = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+
+        let mut blended_pixel = Rgba([0, 0, 0, (alpha * 255.0).round() as u8]);
+        for channel in 0..3 {
+            let source = watermark_pixel[channel] as f32 / 255.0;
+            let backdrop = image_pixel[channel] as f32 / 255.0;
+
+            // the blend only applies where the image is opaque, over transparency the watermark stays normal
+            let mixed = (1.0 - backdrop_alpha) * source + backdrop_alpha * blend_mode.blend_channel(backdrop, source);
+            let composited = (source_alpha * mixed + backdrop_alpha * (1.0 - source_alpha) * backdrop) / alpha;
+            blended_pixel[channel] = (composited.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        image.put_pixel(pixel_x as u32, pixel_y as u32, blended_pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} should be {}", actual, expected);
+    }
+
+    #[test]
+    fn blends_channels_like_the_spec() {
+        assert_close(BlendMode::Normal.blend_channel(0.2, 0.7), 0.7);
+        assert_close(BlendMode::Screen.blend_channel(0.5, 0.5), 0.75);
+        assert_close(BlendMode::Multiply.blend_channel(0.5, 0.5), 0.25);
+        assert_close(BlendMode::Difference.blend_channel(0.2, 0.7), 0.5);
+        // overlay multiplies dark backdrops and screens light ones
+        assert_close(BlendMode::Overlay.blend_channel(0.25, 0.5), 0.25);
+        assert_close(BlendMode::Overlay.blend_channel(0.75, 0.5), 0.75);
+        // a mid grey soft light source leaves the backdrop as it is
+        for backdrop in [0.1, 0.5, 0.9] {
+            assert_close(BlendMode::SoftLight.blend_channel(backdrop, 0.5), backdrop);
+        }
+    }
+
+    #[test]
+    fn keeps_the_watermark_normal_over_transparency() {
+        let mut image = DynamicImage::ImageRgba8(RgbaImage::new(1, 1));
+        let watermark = RgbaImage::from_pixel(1, 1, Rgba([200, 100, 50, 255]));
+
+        blend_overlay(&mut image, &watermark, 0, 0, BlendMode::Multiply);
+        assert_eq!(image.get_pixel(0, 0), Rgba([200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn skips_the_parts_outside_the_image() {
+        let mut image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255])));
+        let watermark = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+
+        blend_overlay(&mut image, &watermark, 1, 1, BlendMode::Multiply);
+        assert_eq!(image.get_pixel(1, 1), Rgba([0, 0, 0, 255]));
+        for (x, y) in [(0, 0), (1, 0), (0, 1)] {
+            assert_eq!(image.get_pixel(x, y), Rgba([255, 255, 255, 255]));
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Condvar,
+    Mutex,
+};
+
+// lets another thread pause, resume or cancel a running batch, the workers check it between images
+#[derive(Debug, Default)]
+pub struct BatchControl {
+    cancelled:  AtomicBool,
+    paused:     Mutex<bool>,
+    resumed:    Condvar,
+}
+
+impl BatchControl {
+    // images that haven't started yet are skipped, the ones in progress stop before they're written
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        // taking the lock first so a worker can't miss the wake up between its check and its wait
+        let _paused = self.paused.lock().unwrap_or_else(|error| error.into_inner());
+        self.resumed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // images in progress are finished, the next ones wait for `resume`
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap_or_else(|error| error.into_inner()) = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap_or_else(|error| error.into_inner()) = false;
+        self.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    // blocks while the batch is paused, returns whether it may continue
+    pub(crate) fn wait_while_paused(&self) -> bool {
+        let mut paused = self.paused.lock().unwrap_or_else(|error| error.into_inner());
+        while *paused && !self.is_cancelled() {
+            paused = self.resumed.wait(paused).unwrap_or_else(|error| error.into_inner());
+        }
+        !self.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn pauses_and_resumes() {
+        let control = BatchControl::default();
+        assert!(!control.is_paused());
+
+        control.pause();
+        assert!(control.is_paused());
+        control.resume();
+        assert!(!control.is_paused());
+        assert!(control.wait_while_paused());
+    }
+
+    #[test]
+    fn resuming_lets_a_paused_worker_continue() {
+        let control = Arc::new(BatchControl::default());
+        control.pause();
+
+        let worker = thread::spawn({
+            let control = Arc::clone(&control);
+            move || control.wait_while_paused()
+        });
+        control.resume();
+        assert!(worker.join().unwrap());
+    }
+
+    #[test]
+    fn cancelling_wakes_a_paused_worker() {
+        let control = Arc::new(BatchControl::default());
+        control.pause();
+
+        let worker = thread::spawn({
+            let control = Arc::clone(&control);
+            move || control.wait_while_paused()
+        });
+        control.cancel();
+        assert!(!worker.join().unwrap());
+        assert!(control.is_cancelled());
+    }
+}
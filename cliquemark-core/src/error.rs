@@ -0,0 +1,63 @@
+use std::{
+    fmt,
+    io,
+    path::PathBuf,
+};
+
+use image::ImageError;
+
+#[derive(Debug)]
+pub enum WatermarkError {
+    Io(io::Error),
+    Image(ImageError),
+    NoImagesFound(PathBuf),
+    InvalidFileName(PathBuf),
+    TargetFolder(io::Error),
+    Font(String),
+    EmptyText,
+    InvalidTemplate(String),
+    // the image would be written to `target`, which is already the output of `other_source`
+    NameCollision { target: PathBuf, other_source: PathBuf },
+    Cancelled,
+}
+
+impl fmt::Display for WatermarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatermarkError::Io(error) => write!(f, "{}", error),
+            WatermarkError::Image(error) => write!(f, "{}", error),
+            WatermarkError::NoImagesFound(folder) => write!(f, "No images found in {}", folder.display()),
+            WatermarkError::InvalidFileName(path) => write!(f, "Invalid file name: {}", path.display()),
+            WatermarkError::TargetFolder(error) => write!(f, "Failed to create directory: {}", error),
+            WatermarkError::Font(message) => write!(f, "{}", message),
+            WatermarkError::EmptyText => write!(f, "The text watermark is empty"),
+            WatermarkError::InvalidTemplate(message) => write!(f, "Invalid file name template: {}", message),
+            WatermarkError::NameCollision { target, other_source } => {
+                write!(f, "{} would also be written by {}", target.display(), other_source.display())
+            }
+            WatermarkError::Cancelled => write!(f, "The batch was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for WatermarkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WatermarkError::Io(error) | WatermarkError::TargetFolder(error) => Some(error),
+            WatermarkError::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WatermarkError {
+    fn from(error: io::Error) -> Self {
+        WatermarkError::Io(error)
+    }
+}
+
+impl From<ImageError> for WatermarkError {
+    fn from(error: ImageError) -> Self {
+        WatermarkError::Image(error)
+    }
+}
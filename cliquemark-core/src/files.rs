@@ -0,0 +1,215 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    metadata::Orientation as ImageOrientation,
+    DynamicImage,
+    ImageDecoder,
+    ImageReader,
+};
+
+use crate::{ImageMetadata, WatermarkError};
+
+pub fn is_image_file(path: &Path) -> bool {
+    if let Some(extension) = path.extension() {
+        let ext = extension.to_string_lossy().to_lowercase();
+        matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "avif" | "ico")
+    } else {
+        false
+    }
+}
+
+// name of the output folder created inside the input folder, numbered when it already exists
+pub const TARGET_FOLDER_NAME: &str = "watermarked";
+
+// output folders of earlier runs, never walked into so their images aren't watermarked twice
+fn is_target_folder(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|folder_name| folder_name.to_string_lossy().starts_with(TARGET_FOLDER_NAME))
+}
+
+fn collect_folder(folder: &Path, recursive: bool, image_entries: &mut Vec<PathBuf>) -> Result<(), WatermarkError> {
+    for entry in fs::read_dir(folder)? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+
+        if is_image_file(&path) {
+            image_entries.push(path);
+        } else if recursive && entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && !is_target_folder(&path) {
+            // unreadable sub-folders are skipped instead of failing the whole batch,
+            // symlinked folders aren't followed so links back up the tree can't loop
+            let _ = collect_folder(&path, recursive, image_entries);
+        }
+    }
+    Ok(())
+}
+
+// all images inside the folder, with `recursive` also those in its sub-folders,
+// sorted so batches run in a stable order
+pub fn collect_image_files(folder: &Path, recursive: bool) -> Result<Vec<PathBuf>, WatermarkError> {
+    let mut image_entries = Vec::new();
+    collect_folder(folder, recursive, &mut image_entries)?;
+
+    if image_entries.is_empty() {
+        return Err(WatermarkError::NoImagesFound(folder.to_path_buf()));
+    }
+    image_entries.sort();
+    Ok(image_entries)
+}
+
+// images of any mix of folders and image files, the folders are collected like `collect_image_files`,
+// every image is taken once and in the order of `inputs`
+pub fn collect_input_files(inputs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>, WatermarkError> {
+    let mut image_entries = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            match collect_image_files(input, recursive) {
+                Ok(folder_entries) => image_entries.extend(folder_entries),
+                // an empty folder doesn't matter as long as the other inputs have images
+                Err(WatermarkError::NoImagesFound(_)) => {}
+                Err(error) => return Err(error),
+            }
+        } else if is_image_file(input) {
+            image_entries.push(input.clone());
+        }
+    }
+
+    let mut seen_entries = HashSet::new();
+    image_entries.retain(|image_entry| seen_entries.insert(image_entry.clone()));
+
+    if image_entries.is_empty() {
+        let folder = common_folder(inputs).unwrap_or_default();
+        return Err(WatermarkError::NoImagesFound(folder));
+    }
+    Ok(image_entries)
+}
+
+// deepest folder containing all inputs, None when they don't share one like folders on different drives
+pub fn common_folder(inputs: &[PathBuf]) -> Option<PathBuf> {
+    let mut folders = inputs.iter().map(|input| match input.is_dir() {
+        true => input.as_path(),
+        false => input.parent().unwrap_or(Path::new("")),
+    });
+
+    let first_folder = folders.next()?.to_path_buf();
+    let common_folder = folders.fold(first_folder, |common_folder, folder| {
+        common_folder
+            .ancestors()
+            .find(|ancestor| folder.starts_with(ancestor))
+            .unwrap_or(Path::new(""))
+            .to_path_buf()
+    });
+    (!common_folder.as_os_str().is_empty()).then_some(common_folder)
+}
+
+// decodes an image and applies its embedded orientation
+pub fn load_image(path: &Path) -> Result<DynamicImage, WatermarkError> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(ImageOrientation::NoTransforms);
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+// like `load_image`, also returning the metadata to write into the watermarked copy,
+// its Exif orientation is reset because the pixels are already turned upright
+pub fn load_image_with_metadata(path: &Path) -> Result<(DynamicImage, ImageMetadata), WatermarkError> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format();
+    let mut decoder = reader.into_decoder()?;
+
+    // unreadable metadata is dropped rather than failing the image
+    let mut metadata = ImageMetadata {
+        format,
+        icc_profile: decoder.icc_profile().ok().flatten(),
+        exif: decoder.exif_metadata().ok().flatten(),
+        xmp: decoder.xmp_metadata().ok().flatten(),
+        iptc: decoder.iptc_metadata().ok().flatten(),
+    };
+    let orientation = decoder.orientation().unwrap_or(ImageOrientation::NoTransforms);
+    if let Some(exif) = &mut metadata.exif {
+        let _ = ImageOrientation::remove_from_exif_chunk(exif);
+    }
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok((image, metadata))
+}
+
+// creates `base_name` inside `target_parent`, or `base_name1`, `base_name2`, ... when it already exists
+pub fn create_target_folder(base_name: &str, target_parent: &Path) -> Result<PathBuf, WatermarkError> {
+    let mut i = 0;
+
+    loop {
+        let folder_name = match i {
+            0 => base_name.to_string(),
+            _ => format!("{}{}", base_name, i),
+        };
+        let target_folder = target_parent.join(folder_name);
+
+        match fs::create_dir(&target_folder) {
+            Ok(()) => return Ok(target_folder),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => i += 1,
+            Err(error) => return Err(WatermarkError::TargetFolder(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // files named like images, their content isn't read while collecting
+    fn folder_with(name: &str, files: &[&str]) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("cliquemark-files-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        for file in files {
+            let path = folder.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, []).unwrap();
+        }
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn skips_earlier_output_folders() {
+        let folder = folder_with("output", &["b.png", "a.jpg", "notes.txt", "sub/c.png", "watermarked/d.png", "watermarked2/e.png"]);
+
+        let recursive = collect_image_files(&folder, true);
+        let flat = collect_image_files(&folder, false);
+        let _ = fs::remove_dir_all(&folder);
+
+        assert_eq!(recursive.unwrap(), [folder.join("a.jpg"), folder.join("b.png"), folder.join("sub/c.png")]);
+        assert_eq!(flat.unwrap(), [folder.join("a.jpg"), folder.join("b.png")]);
+    }
+
+    #[test]
+    fn reports_folders_without_images() {
+        let folder = folder_with("empty", &["notes.txt"]);
+
+        let image_entries = collect_image_files(&folder, true);
+        let _ = fs::remove_dir_all(&folder);
+
+        assert!(matches!(image_entries, Err(WatermarkError::NoImagesFound(_))));
+    }
+
+    #[test]
+    fn takes_every_input_image_once() {
+        let folder = folder_with("inputs", &["a.png", "b.png"]);
+
+        let image_entries = collect_input_files(&[folder.join("b.png"), folder.clone()], false);
+        let _ = fs::remove_dir_all(&folder);
+
+        assert_eq!(image_entries.unwrap(), [folder.join("b.png"), folder.join("a.png")]);
+    }
+}
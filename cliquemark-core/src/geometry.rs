@@ -0,0 +1,203 @@
+use image::{
+    imageops::{self, FilterType::Lanczos3},
+    DynamicImage,
+    Rgba,
+    RgbaImage,
+};
+
+use crate::Anchor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    // scales images down until they fit, smaller images are left alone, None doesn't limit that side
+    Fit { max_width: Option<u32>, max_height: Option<u32> },
+    // stretches when the aspect ratio differs, crop or pad to the same ratio first to avoid that
+    Exact { width: u32, height: u32 },
+}
+
+impl Resize {
+    fn dimensions(self, [width, height]: [u32; 2]) -> [u32; 2] {
+        match self {
+            Resize::Fit { max_width, max_height } => {
+                let scale = [(max_width, width), (max_height, height)]
+                    .into_iter()
+                    .filter_map(|(max_length, length)| max_length.map(|max_length| max_length as f64 / length as f64))
+                    .fold(1.0, f64::min);
+
+                [width, height].map(|length| ((length as f64 * scale).round() as u32).max(1))
+            }
+            Resize::Exact { width, height } => [width.max(1), height.max(1)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reframe {
+    // cuts the image down to `aspect_ratio`, keeping the part at `gravity`
+    Crop { aspect_ratio: [u32; 2], gravity: Anchor },
+    // extends the image to `aspect_ratio` with straight RGBA `color` bars, keeping it centred
+    Pad { aspect_ratio: [u32; 2], color: [u8; 4] },
+}
+
+impl Reframe {
+    fn dimensions(self, [width, height]: [u32; 2]) -> [u32; 2] {
+        let (aspect_ratio, grow) = match self {
+            Reframe::Crop { aspect_ratio, .. } => (aspect_ratio, false),
+            Reframe::Pad { aspect_ratio, .. } => (aspect_ratio, true),
+        };
+        let [aspect_width, aspect_height] = aspect_ratio.map(|length| length.max(1) as f64);
+
+        // cropping a wider image or padding a taller one changes its width, otherwise its height
+        let wider = width as f64 * aspect_height > height as f64 * aspect_width;
+        if wider != grow {
+            [((height as f64 * aspect_width / aspect_height).round() as u32).max(1), height]
+        } else {
+            [width, ((width as f64 * aspect_height / aspect_width).round() as u32).max(1)]
+        }
+    }
+
+    fn apply(self, image: DynamicImage) -> DynamicImage {
+        let [width, height] = self.dimensions([image.width(), image.height()]);
+        if [width, height] == [image.width(), image.height()] {
+            return image;
+        }
+
+        match self {
+            Reframe::Crop { gravity, .. } => {
+                let [x, y] = gravity.position(
+                    [image.width() as i64, image.height() as i64],
+                    [width as i64, height as i64],
+                    0,
+                    [0, 0],
+                );
+                image.crop_imm(x.max(0) as u32, y.max(0) as u32, width, height)
+            }
+            Reframe::Pad { color, .. } => {
+                let mut canvas = RgbaImage::from_pixel(width, height, Rgba(color));
+                let x = (width - image.width()) / 2;
+                let y = (height - image.height()) / 2;
+                imageops::replace(&mut canvas, &image.to_rgba8(), x as i64, y as i64);
+
+                // opaque bars around an opaque image don't need an alpha channel
+                if color[3] == u8::MAX && !image.color().has_alpha() {
+                    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).into_rgb8())
+                } else {
+                    DynamicImage::ImageRgba8(canvas)
+                }
+            }
+        }
+    }
+}
+
+// changes to the image's size and framing made before it's watermarked,
+// the reframing runs first so an exact size can follow a crop to the same aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Geometry {
+    pub reframe:    Option<Reframe>,
+    pub resize:     Option<Resize>,
+}
+
+impl Geometry {
+    // size of a `dimensions` image after the geometry is applied
+    pub fn dimensions(&self, dimensions: [u32; 2]) -> [u32; 2] {
+        let dimensions = self.reframe.map_or(dimensions, |reframe| reframe.dimensions(dimensions));
+        self.resize.map_or(dimensions, |resize| resize.dimensions(dimensions))
+    }
+
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = match self.reframe {
+            Some(reframe) => reframe.apply(image),
+            None => image,
+        };
+        let Some(resize) = self.resize else {
+            return image;
+        };
+
+        let [width, height] = resize.dimensions([image.width(), image.height()]);
+        if [width, height] == [image.width(), image.height()] {
+            return image;
+        }
+        image.resize_exact(width, height, Lanczos3)
+    }
+
+    // the geometry applied to `preview`, a downscaled copy of a `source_dimensions` image,
+    // the result has the final aspect ratio but stays at the copy's resolution
+    pub fn apply_preview(&self, preview: DynamicImage, source_dimensions: [u32; 2]) -> DynamicImage {
+        let preview = match self.reframe {
+            Some(reframe) => reframe.apply(preview),
+            None => preview,
+        };
+
+        // only an exact size can change the aspect ratio once more
+        let Some(Resize::Exact { .. }) = self.resize else {
+            return preview;
+        };
+        let [final_width, final_height] = self.dimensions(source_dimensions);
+        let preview_scale = (preview.width() as f64 / final_width as f64).min(preview.height() as f64 / final_height as f64);
+        preview.resize_exact(
+            ((final_width as f64 * preview_scale).round() as u32).max(1),
+            ((final_height as f64 * preview_scale).round() as u32).max(1),
+            Lanczos3,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    // red left half, blue right half
+    fn halves(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| if x < width / 2 { RED } else { BLUE }))
+    }
+
+    #[test]
+    fn crops_to_the_part_at_the_gravity() {
+        let crop = |gravity| Reframe::Crop { aspect_ratio: [1, 1], gravity }.apply(halves(4, 2));
+
+        let left = crop(Anchor::CenterLeft);
+        assert_eq!(left.dimensions(), (2, 2));
+        assert!(left.pixels().all(|(_, _, pixel)| pixel == RED));
+
+        let right = crop(Anchor::BottomRight);
+        assert_eq!(right.dimensions(), (2, 2));
+        assert!(right.pixels().all(|(_, _, pixel)| pixel == BLUE));
+    }
+
+    #[test]
+    fn pads_around_the_centred_image() {
+        let image = DynamicImage::ImageRgb8(halves(4, 2).into_rgb8());
+        let padded = Reframe::Pad { aspect_ratio: [1, 1], color: [0, 255, 0, 255] }.apply(image);
+
+        assert_eq!(padded.dimensions(), (4, 4));
+        assert_eq!(padded.get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(padded.get_pixel(0, 1), RED);
+        assert_eq!(padded.get_pixel(3, 2), BLUE);
+        assert_eq!(padded.get_pixel(3, 3), Rgba([0, 255, 0, 255]));
+        // opaque bars around an opaque image
+        assert!(!padded.color().has_alpha());
+    }
+
+    #[test]
+    fn fitting_only_scales_down() {
+        let fit = Geometry { reframe: None, resize: Some(Resize::Fit { max_width: Some(100), max_height: None }) };
+
+        assert_eq!(fit.dimensions([400, 200]), [100, 50]);
+        assert_eq!(fit.dimensions([50, 20]), [50, 20]);
+    }
+
+    #[test]
+    fn reframes_before_resizing() {
+        let geometry = Geometry {
+            reframe: Some(Reframe::Crop { aspect_ratio: [16, 9], gravity: Anchor::Center }),
+            resize: Some(Resize::Fit { max_width: Some(1600), max_height: Some(1600) }),
+        };
+
+        assert_eq!(geometry.dimensions([4000, 3000]), [1600, 900]);
+    }
+}
@@ -0,0 +1,75 @@
+mod blend;
+mod control;
+mod error;
+mod files;
+mod geometry;
+mod metadata;
+mod naming;
+mod output;
+mod text;
+mod watermark;
+
+pub use image;
+
+pub use blend::{
+    blend_overlay,
+    BlendMode,
+};
+pub use control::BatchControl;
+pub use error::WatermarkError;
+pub use files::{
+    collect_image_files,
+    collect_input_files,
+    common_folder,
+    create_target_folder,
+    is_image_file,
+    load_image,
+    load_image_with_metadata,
+    TARGET_FOLDER_NAME,
+};
+pub use geometry::{
+    Geometry,
+    Reframe,
+    Resize,
+};
+pub use metadata::{
+    save_image,
+    ImageMetadata,
+};
+pub use naming::{
+    target_paths,
+    NameTemplate,
+    NAME_TOKENS,
+};
+pub use output::{
+    flatten,
+    has_alpha_support,
+    OutputFormat,
+    OutputSettings,
+    PngCompression,
+};
+pub use text::{
+    system_font_families,
+    FontSource,
+    TextRenderer,
+    TextWatermark,
+};
+pub use watermark::{
+    apply_opacity,
+    apply_watermark,
+    preview_watermark_rectangle,
+    render_preview,
+    retry_watermark,
+    rotate_watermark,
+    rotated_dimensions,
+    tile_positions,
+    watermark_image,
+    watermark_layer,
+    Anchor,
+    Progress,
+    Tiling,
+    WatermarkJob,
+    WatermarkResult,
+    WatermarkSettings,
+    WatermarkSource,
+};
@@ -0,0 +1,228 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::Cursor,
+    path::Path,
+};
+
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        jpeg::JpegEncoder,
+        png::{FilterType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    error::{EncodingError, ImageError},
+    DynamicImage,
+    ImageEncoder,
+    ImageFormat,
+    ImageResult,
+};
+use img_parts::{
+    jpeg::{markers, Jpeg, JpegSegment},
+    png::{Png, PngChunk},
+    riff::{RiffChunk, RiffContent},
+    webp::{WebP, CHUNK_ALPH, CHUNK_EXIF, CHUNK_ICCP, CHUNK_VP8X, CHUNK_XMP},
+    Bytes,
+};
+
+use crate::{OutputSettings, WatermarkError};
+
+const JPEG_XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const JPEG_IPTC_PREFIX: &[u8] = b"Photoshop 3.0\0";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const PNG_IPTC_KEYWORD: &[u8] = b"Raw profile type iptc";
+// a JPEG segment holds at most 65535 bytes including its 2 length bytes
+const JPEG_SEGMENT_MAX_SIZE: usize = 65533;
+// VP8X flags telling readers which chunks a WebP has
+const WEBP_ICC_FLAG: u8 = 0b0010_0000;
+const WEBP_ALPHA_FLAG: u8 = 0b0001_0000;
+const WEBP_EXIF_FLAG: u8 = 0b0000_1000;
+const WEBP_XMP_FLAG: u8 = 0b0000_0100;
+
+// metadata carried from a source image to its watermarked copy, as the raw blocks the decoder found
+#[derive(Debug, Clone, Default)]
+pub struct ImageMetadata {
+    // format of the source image
+    pub format:         Option<ImageFormat>,
+    pub icc_profile:    Option<Vec<u8>>,
+    // TIFF structured Exif, without the "Exif\0\0" header
+    pub exif:           Option<Vec<u8>>,
+    // XMP packet
+    pub xmp:            Option<Vec<u8>>,
+    // in the source format's own encoding (a Photoshop resource block for JPEGs, a hex text profile for PNGs),
+    // so it is only written to images of the same format
+    pub iptc:           Option<Vec<u8>>,
+}
+
+fn write_with_metadata(image: &DynamicImage, mut encoder: impl ImageEncoder, metadata: &ImageMetadata) -> ImageResult<()> {
+    // encoders without support for a block leave it out
+    if let Some(icc_profile) = &metadata.icc_profile {
+        let _ = encoder.set_icc_profile(icc_profile.clone());
+    }
+    if let Some(exif) = &metadata.exif {
+        let _ = encoder.set_exif_metadata(exif.clone());
+    }
+    image.write_with_encoder(encoder)
+}
+
+// libwebp writes no metadata, so the ICC profile and Exif are added to its output by hand,
+// in the extended format that the text metadata is added to as well
+fn write_lossy_webp(image: &DynamicImage, quality: u8, metadata: &ImageMetadata) -> ImageResult<Vec<u8>> {
+    let (width, height) = (image.width(), image.height());
+    let encoded = if image.color().has_alpha() {
+        webp::Encoder::from_rgba(image.to_rgba8().as_raw(), width, height).encode_simple(false, quality as f32)
+    } else {
+        webp::Encoder::from_rgb(image.to_rgb8().as_raw(), width, height).encode_simple(false, quality as f32)
+    };
+    let encoded = Bytes::copy_from_slice(&encoded.map_err(|error| {
+        ImageError::Encoding(EncodingError::new(ImageFormat::WebP.into(), format!("{:?}", error)))
+    })?);
+
+    if metadata.icc_profile.is_none() && metadata.exif.is_none() {
+        return Ok(encoded.to_vec());
+    }
+    let Ok(mut webp) = WebP::from_bytes(encoded.clone()) else {
+        return Ok(encoded.to_vec());
+    };
+
+    let mut flags = [0; 10];
+    // canvas width and height minus one as 24 bit little endian
+    flags[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+    flags[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+    if webp.has_chunk(CHUNK_ALPH) {
+        flags[0] |= WEBP_ALPHA_FLAG;
+    }
+    if metadata.icc_profile.is_some() {
+        flags[0] |= WEBP_ICC_FLAG;
+    }
+    if metadata.exif.is_some() {
+        flags[0] |= WEBP_EXIF_FLAG;
+    }
+
+    // VP8X comes first and the ICC profile directly after it, Exif goes behind the image data
+    webp.remove_chunks_by_id(CHUNK_VP8X);
+    webp.chunks_mut().insert(0, RiffChunk::new(CHUNK_VP8X, RiffContent::Data(Bytes::copy_from_slice(&flags))));
+    if let Some(icc_profile) = &metadata.icc_profile {
+        webp.chunks_mut().insert(1, RiffChunk::new(CHUNK_ICCP, RiffContent::Data(Bytes::from(icc_profile.clone()))));
+    }
+    if let Some(exif) = &metadata.exif {
+        webp.chunks_mut().push(RiffChunk::new(CHUNK_EXIF, RiffContent::Data(Bytes::from(exif.clone()))));
+    }
+
+    let mut added = Vec::new();
+    match webp.encoder().write_to(&mut added) {
+        Ok(_) => Ok(added),
+        // the encoded image is still valid, only without the metadata
+        Err(_) => Ok(encoded.to_vec()),
+    }
+}
+
+// APPn segments directly after SOI, metadata goes behind them and before the image data
+fn insert_jpeg_segments(jpeg: &mut Jpeg, new_segments: Vec<JpegSegment>) {
+    let position = jpeg
+        .segments()
+        .iter()
+        .position(|segment| !(markers::APP0..=markers::APP15).contains(&segment.marker()))
+        .unwrap_or(jpeg.segments().len());
+
+    for (i, segment) in new_segments.into_iter().enumerate() {
+        jpeg.segments_mut().insert(position + i, segment);
+    }
+}
+
+fn prefixed(prefix: &[u8], data: &[u8]) -> Bytes {
+    Bytes::from([prefix, data].concat())
+}
+
+// XMP and IPTC aren't written by the `image` encoders, they are added to the encoded file
+fn add_text_metadata(encoded: Bytes, format: ImageFormat, metadata: &ImageMetadata) -> Bytes {
+    let iptc = metadata.iptc.as_ref().filter(|_| metadata.format == Some(format));
+    if metadata.xmp.is_none() && iptc.is_none() {
+        return encoded;
+    }
+
+    let mut added = Vec::new();
+    let written = match format {
+        ImageFormat::Jpeg => Jpeg::from_bytes(encoded.clone()).ok().map(|mut jpeg| {
+            let mut segments = Vec::new();
+            if let Some(xmp) = metadata.xmp.as_ref().filter(|xmp| JPEG_XMP_PREFIX.len() + xmp.len() <= JPEG_SEGMENT_MAX_SIZE) {
+                segments.push(JpegSegment::new_with_contents(markers::APP1, prefixed(JPEG_XMP_PREFIX, xmp)));
+            }
+            if let Some(iptc) = iptc.filter(|iptc| JPEG_IPTC_PREFIX.len() + iptc.len() <= JPEG_SEGMENT_MAX_SIZE) {
+                segments.push(JpegSegment::new_with_contents(markers::APP13, prefixed(JPEG_IPTC_PREFIX, iptc)));
+            }
+            insert_jpeg_segments(&mut jpeg, segments);
+            jpeg.encoder().write_to(&mut added)
+        }),
+        ImageFormat::Png => Png::from_bytes(encoded.clone()).ok().map(|mut png| {
+            // text chunks may go anywhere after the header
+            if let Some(xmp) = &metadata.xmp {
+                // keyword, uncompressed, no language or translated keyword
+                let header = [PNG_XMP_KEYWORD, b"\0\0\0\0\0"].concat();
+                png.chunks_mut().insert(1, PngChunk::new(*b"iTXt", prefixed(&header, xmp)));
+            }
+            if let Some(iptc) = iptc {
+                let header = [PNG_IPTC_KEYWORD, b"\0"].concat();
+                png.chunks_mut().insert(1, PngChunk::new(*b"tEXt", prefixed(&header, iptc)));
+            }
+            png.encoder().write_to(&mut added)
+        }),
+        // only extended WebPs can hold XMP, those are written when there is an ICC profile, Exif or
+        // transparency in a lossy WebP
+        ImageFormat::WebP => WebP::from_bytes(encoded.clone()).ok().map(|mut webp| {
+            if let (Some(xmp), true) = (&metadata.xmp, webp.has_chunk(CHUNK_VP8X)) {
+                let vp8x = webp.chunks_mut().iter_mut().find(|chunk| chunk.id() == CHUNK_VP8X);
+                if let Some(RiffContent::Data(flags)) = vp8x.map(|chunk| chunk.content_mut()) {
+                    let mut updated_flags = flags.to_vec();
+                    updated_flags[0] |= WEBP_XMP_FLAG;
+                    *flags = Bytes::from(updated_flags);
+                }
+                webp.chunks_mut().push(RiffChunk::new(CHUNK_XMP, RiffContent::Data(Bytes::from(xmp.clone()))));
+            }
+            webp.encoder().write_to(&mut added)
+        }),
+        _ => None,
+    };
+
+    match written {
+        Some(Ok(_)) => Bytes::from(added),
+        // the encoded image is still valid, only without the text metadata
+        _ => encoded,
+    }
+}
+
+// encodes in the format of the file extension with the encoder settings of `output` and keeps
+// the ICC profile, Exif, XMP and IPTC where the format has a place for them
+pub fn save_image(image: &DynamicImage, path: &Path, metadata: &ImageMetadata, output: &OutputSettings) -> Result<(), WatermarkError> {
+    let format = ImageFormat::from_path(path)?;
+    let quality = output.quality.clamp(1, 100);
+    let mut encoded = Vec::new();
+
+    match format {
+        ImageFormat::Jpeg => write_with_metadata(image, JpegEncoder::new_with_quality(&mut encoded, quality), metadata)?,
+        ImageFormat::Png => {
+            let encoder = PngEncoder::new_with_quality(&mut encoded, output.png_compression.compression_type(), FilterType::default());
+            write_with_metadata(image, encoder, metadata)?
+        }
+        ImageFormat::WebP if output.webp_lossless => write_with_metadata(image, WebPEncoder::new_lossless(&mut encoded), metadata)?,
+        ImageFormat::WebP => encoded = write_lossy_webp(image, quality, metadata)?,
+        // `cavif`'s default speed
+        ImageFormat::Avif => write_with_metadata(image, AvifEncoder::new_with_speed_quality(&mut encoded, 4, quality), metadata)?,
+        _ => image.write_to(&mut Cursor::new(&mut encoded), format)?,
+    }
+
+    // written next to the target and renamed, so a failed or interrupted write never leaves a truncated image
+    let mut partial_file_name = OsString::from(".");
+    partial_file_name.push(path.file_name().unwrap_or_default());
+    partial_file_name.push(".part");
+    let partial_path = path.with_file_name(partial_file_name);
+
+    let written = fs::write(&partial_path, add_text_metadata(Bytes::from(encoded), format, metadata))
+        .and_then(|()| fs::rename(&partial_path, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+    written?;
+    Ok(())
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+use exif::{In, Tag, Value};
+use image::{
+    metadata::Orientation as ImageOrientation,
+    ImageDecoder,
+    ImageReader,
+};
+use rayon::prelude::*;
+
+use crate::{Geometry, OutputFormat, WatermarkError, WatermarkJob};
+
+// every token of a name template with what it's replaced by, for help texts
+pub const NAME_TOKENS: [(&str, &str); 12] = [
+    ("{stem}", "original file name without extension"),
+    ("{ext}", "original extension"),
+    ("{counter}", "position in the batch, {counter:04} pads it to 4 digits"),
+    ("{date}", "capture date as 2026-01-31"),
+    ("{time}", "capture time as 14-05-59"),
+    ("{year}", "capture year"),
+    ("{month}", "capture month"),
+    ("{day}", "capture day"),
+    ("{camera}", "camera model"),
+    ("{width}", "width of the output image"),
+    ("{height}", "height of the output image"),
+    ("{folder}", "name of the folder the image is in"),
+];
+
+// written for Exif values an image doesn't have
+const UNKNOWN_VALUE: &str = "unknown";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Stem,
+    Extension,
+    Counter { width: usize },
+    Date,
+    Time,
+    Year,
+    Month,
+    Day,
+    Camera,
+    Width,
+    Height,
+    Folder,
+}
+
+impl Token {
+    fn parse(token: &str) -> Option<Token> {
+        let token = match token.split_once(':') {
+            Some(("counter", width)) => return width.parse().ok().map(|width| Token::Counter { width }),
+            Some(_) => return None,
+            None => match token {
+                "stem" => Token::Stem,
+                "ext" => Token::Extension,
+                "counter" => Token::Counter { width: 0 },
+                "date" => Token::Date,
+                "time" => Token::Time,
+                "year" => Token::Year,
+                "month" => Token::Month,
+                "day" => Token::Day,
+                "camera" => Token::Camera,
+                "width" => Token::Width,
+                "height" => Token::Height,
+                "folder" => Token::Folder,
+                _ => return None,
+            },
+        };
+        Some(token)
+    }
+
+    // tokens filled in from the image file rather than its path
+    fn needs_header(self) -> bool {
+        !matches!(self, Token::Stem | Token::Extension | Token::Counter { .. } | Token::Folder)
+    }
+
+    fn value(self, fields: &NameFields) -> String {
+        let captured = |format: fn([u16; 6]) -> String| fields.captured.map_or(UNKNOWN_VALUE.to_string(), format);
+
+        match self {
+            Token::Stem => fields.stem.clone(),
+            Token::Extension => fields.extension.clone(),
+            Token::Counter { width } => format!("{:0width$}", fields.counter, width = width),
+            Token::Date => captured(|[year, month, day, ..]| format!("{:04}-{:02}-{:02}", year, month, day)),
+            Token::Time => captured(|[.., hour, minute, second]| format!("{:02}-{:02}-{:02}", hour, minute, second)),
+            Token::Year => captured(|[year, ..]| format!("{:04}", year)),
+            Token::Month => captured(|[_, month, ..]| format!("{:02}", month)),
+            Token::Day => captured(|[_, _, day, ..]| format!("{:02}", day)),
+            Token::Camera => fields.camera.clone().unwrap_or_else(|| UNKNOWN_VALUE.to_string()),
+            Token::Width => fields.dimensions[0].to_string(),
+            Token::Height => fields.dimensions[1].to_string(),
+            Token::Folder => fields.folder.clone(),
+        }
+    }
+}
+
+// token values can't add folders or characters some file systems reject
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            character if character.is_control() => '_',
+            character => character,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Token(Token),
+}
+
+// output name like `{client}_{date}_{counter:04}`, a '/' puts the images in sub-folders of the
+// target folder and the extension of the output format is always added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<NameTemplate, WatermarkError> {
+        let invalid = |message: &str| Err(WatermarkError::InvalidTemplate(message.to_string()));
+
+        if template.trim().is_empty() {
+            return invalid("the name is empty");
+        }
+        // a '\' separates folders on Windows only, so it's refused everywhere
+        if template.contains('\\') {
+            return invalid("use '/' to put the images in folders");
+        }
+        // checked per folder the way `render` splits them
+        let escapes = template.split('/').map(str::trim).any(|folder| {
+            Path::new(folder)
+                .components()
+                .any(|component| matches!(component, Component::ParentDir | Component::RootDir | Component::Prefix(_)))
+        });
+        if template.starts_with('/') || escapes {
+            return invalid("names have to stay inside the output folder");
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return invalid("'}' without '{'");
+            }
+            let Some(length) = rest[start..].find('}') else {
+                return invalid("'{' without '}'");
+            };
+
+            let token = &rest[start + 1..start + length];
+            let Some(token) = Token::parse(token) else {
+                return Err(WatermarkError::InvalidTemplate(format!("unknown token {{{}}}", token)));
+            };
+
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            segments.push(Segment::Token(token));
+            rest = &rest[start + length + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(NameTemplate { segments })
+    }
+
+    fn needs_header(&self) -> bool {
+        self.segments.iter().any(|segment| matches!(segment, Segment::Token(token) if token.needs_header()))
+    }
+
+    // relative path without extension, empty folder names are dropped
+    fn render(&self, fields: &NameFields) -> PathBuf {
+        let name = self.segments.iter().map(|segment| match segment {
+            Segment::Text(text) => text.clone(),
+            Segment::Token(token) => sanitize(&token.value(fields)),
+        }).collect::<String>();
+
+        name.split('/').map(str::trim).filter(|folder| !folder.is_empty()).collect()
+    }
+
+    // output path relative to the target folder for `image_entry`, the `counter`th image of the batch
+    pub fn target_name(
+        &self,
+        image_entry:    &Path,
+        counter:        usize,
+        geometry:       &Geometry,
+        format:         OutputFormat,
+    ) -> Result<PathBuf, WatermarkError>
+    {
+        let fields = NameFields::read(image_entry, counter, geometry, self.needs_header())?;
+        let name = self.render(&fields);
+
+        // appended instead of using `set_extension` so dots inside the name are kept
+        let extension = format.image_format().map_or(fields.extension, |format| format.extensions_str()[0].to_string());
+        let mut name = OsString::from(name);
+        if !extension.is_empty() {
+            name.push(".");
+            name.push(extension);
+        }
+        Ok(PathBuf::from(name))
+    }
+}
+
+// values the tokens of a template are replaced by
+#[derive(Debug, Clone, Default)]
+struct NameFields {
+    stem:       String,
+    extension:  String,
+    counter:    usize,
+    folder:     String,
+    // year, month, day, hour, minute and second of the Exif capture date
+    captured:   Option<[u16; 6]>,
+    camera:     Option<String>,
+    // of the output, after the geometry
+    dimensions: [u32; 2],
+}
+
+impl NameFields {
+    // `with_header` also reads the size and Exif data, only the header of the file is decoded
+    fn read(image_entry: &Path, counter: usize, geometry: &Geometry, with_header: bool) -> Result<NameFields, WatermarkError> {
+        let file_name = |path: Option<&std::ffi::OsStr>| path.map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let mut fields = NameFields {
+            stem: file_name(image_entry.file_stem()),
+            extension: file_name(image_entry.extension()),
+            counter,
+            folder: file_name(image_entry.parent().and_then(Path::file_name)),
+            ..NameFields::default()
+        };
+        if !with_header {
+            return Ok(fields);
+        }
+
+        let mut decoder = ImageReader::open(image_entry)?.with_guessed_format()?.into_decoder()?;
+        let (width, height) = decoder.dimensions();
+        let orientation = decoder.orientation().unwrap_or(ImageOrientation::NoTransforms);
+        let upright_dimensions = match orientation {
+            ImageOrientation::Rotate90
+            | ImageOrientation::Rotate270
+            | ImageOrientation::Rotate90FlipH
+            | ImageOrientation::Rotate270FlipH => [height, width],
+            _ => [width, height],
+        };
+        fields.dimensions = geometry.dimensions(upright_dimensions);
+
+        // unreadable Exif data leaves the values unknown rather than failing the image
+        let exif = decoder.exif_metadata().ok().flatten().and_then(|exif| exif::Reader::new().read_raw(exif).ok());
+        if let Some(exif) = exif {
+            let ascii = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+                Some(Value::Ascii(values)) => values.first().cloned(),
+                _ => None,
+            };
+
+            fields.captured = ascii(Tag::DateTimeOriginal)
+                .or_else(|| ascii(Tag::DateTime))
+                .and_then(|date_time| exif::DateTime::from_ascii(&date_time).ok())
+                .map(|date_time| [
+                    date_time.year,
+                    date_time.month as u16,
+                    date_time.day as u16,
+                    date_time.hour as u16,
+                    date_time.minute as u16,
+                    date_time.second as u16,
+                ]);
+            fields.camera = ascii(Tag::Model)
+                .map(|model| String::from_utf8_lossy(&model).trim_matches(|character: char| character == '\0' || character.is_whitespace()).to_string())
+                .filter(|model| !model.is_empty());
+        }
+        Ok(fields)
+    }
+}
+
+fn relative_target_path(job: &WatermarkJob, index: usize, image_entry: &Path) -> Result<PathBuf, WatermarkError> {
+    if let Some(name_template) = &job.name_template {
+        return name_template.target_name(image_entry, index + 1, &job.geometry, job.output.format);
+    }
+
+    // without a template the images keep their name and their place in the folder tree
+    let file_name = image_entry
+        .file_name()
+        .ok_or_else(|| WatermarkError::InvalidFileName(image_entry.to_path_buf()))?;
+    let mut relative_path = image_entry.strip_prefix(&job.source_folder).unwrap_or(Path::new(file_name)).to_path_buf();
+    if let Some(format) = job.output.format.image_format() {
+        relative_path.set_extension(format.extensions_str()[0]);
+    }
+    Ok(relative_path)
+}
+
+// output path of every image of the job in the order of `image_entries`,
+// an image whose path was already taken by an earlier one gets a `NameCollision` error instead
+pub fn target_paths(job: &WatermarkJob) -> Vec<Result<PathBuf, WatermarkError>> {
+    let target_paths = job.image_entries
+        .par_iter()
+        .enumerate()
+        .map(|(index, image_entry)| relative_target_path(job, index, image_entry).map(|path| job.target_folder.join(path)))
+        .collect::<Vec<_>>();
+
+    // compared case insensitively because that's how Windows and macOS compare file names
+    let mut taken_paths: HashMap<String, &PathBuf> = HashMap::new();
+    target_paths
+        .into_iter()
+        .zip(&job.image_entries)
+        .map(|(target_path, image_entry)| {
+            let target_path = target_path?;
+            match taken_paths.entry(target_path.to_string_lossy().to_lowercase()) {
+                Entry::Occupied(entry) => Err(WatermarkError::NameCollision {
+                    target: target_path,
+                    other_source: entry.get().to_path_buf(),
+                }),
+                Entry::Vacant(entry) => {
+                    entry.insert(image_entry);
+                    Ok(target_path)
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::RgbImage;
+
+    use super::*;
+    use crate::{Anchor, OutputSettings, WatermarkSettings, WatermarkSource};
+
+    fn job(image_entries: &[&str], name_template: Option<&str>) -> WatermarkJob {
+        WatermarkJob {
+            image_entries: image_entries.iter().map(PathBuf::from).collect(),
+            source_folder: PathBuf::from("photos"),
+            watermark: WatermarkSource::Image(image::DynamicImage::new_rgba8(1, 1)),
+            target_folder: PathBuf::from("watermarked"),
+            geometry: Geometry::default(),
+            settings: WatermarkSettings::from_scale(1.0, 0.0, [100, 100], [10, 10], Anchor::default()),
+            output: OutputSettings::default(),
+            name_template: name_template.map(|template| NameTemplate::parse(template).unwrap()),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in ["", "  ", "/absolute", "up/../and/out", "..", r"..\..\x", r"C:\x", r"\\server\share", "{stem", "stem}", "{unknown}", "{counter:wide}", "{stem:04}"] {
+            assert!(
+                matches!(NameTemplate::parse(template), Err(WatermarkError::InvalidTemplate(_))),
+                "{:?} should be rejected",
+                template,
+            );
+        }
+    }
+
+    #[test]
+    fn pads_the_counter() {
+        let render = |template: &str, counter: usize| {
+            NameTemplate::parse(template).unwrap().render(&NameFields { counter, ..NameFields::default() })
+        };
+
+        assert_eq!(render("image_{counter:04}", 7), PathBuf::from("image_0007"));
+        assert_eq!(render("image_{counter}", 7), PathBuf::from("image_7"));
+        // a counter wider than the padding is kept whole
+        assert_eq!(render("{counter:02}", 123), PathBuf::from("123"));
+    }
+
+    #[test]
+    fn fills_in_unknown_without_exif() {
+        let folder = std::env::temp_dir().join(format!("cliquemark-naming-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let image_entry = folder.join("no exif.png");
+        RgbImage::new(3, 2).save(&image_entry).unwrap();
+
+        let template = NameTemplate::parse("{date}_{time}_{year}_{camera}_{width}x{height}").unwrap();
+        let target_name = template.target_name(&image_entry, 1, &Geometry::default(), OutputFormat::Keep);
+        let _ = fs::remove_dir_all(&folder);
+
+        assert_eq!(target_name.unwrap(), PathBuf::from("unknown_unknown_unknown_unknown_3x2.png"));
+    }
+
+    #[test]
+    fn names_differing_only_in_case_collide() {
+        let target_paths = target_paths(&job(&["photos/A.jpg", "photos/a.JPG"], None));
+
+        assert_eq!(target_paths[0].as_ref().unwrap(), &PathBuf::from("watermarked/A.jpg"));
+        match &target_paths[1] {
+            Err(WatermarkError::NameCollision { target, other_source }) => {
+                assert_eq!(target, &PathBuf::from("watermarked/a.JPG"));
+                assert_eq!(other_source, &PathBuf::from("photos/A.jpg"));
+            }
+            other => panic!("expected a name collision, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn template_names_get_the_output_extension() {
+        let mut job = job(&["photos/a.png", "photos/b.png"], Some("client/{stem}_{counter:02}"));
+        job.output.format = OutputFormat::Jpeg;
+
+        let target_paths = target_paths(&job).into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(target_paths, [PathBuf::from("watermarked/client/a_01.jpg"), PathBuf::from("watermarked/client/b_02.jpg")]);
+    }
+}
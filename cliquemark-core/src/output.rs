@@ -0,0 +1,143 @@
+use image::{
+    codecs::png::CompressionType,
+    DynamicImage,
+    ImageFormat,
+    Rgb,
+    RgbImage,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    // the format of each source image
+    #[default]
+    Keep,
+    Jpeg,
+    Png,
+    // lossless or lossy depending on `OutputSettings::webp_lossless`
+    WebP,
+    Avif,
+}
+
+impl OutputFormat {
+    // the same order as the format dropdown in the settings sidebar
+    pub const ALL: [OutputFormat; 5] = [
+        OutputFormat::Keep,
+        OutputFormat::Jpeg,
+        OutputFormat::Png,
+        OutputFormat::WebP,
+        OutputFormat::Avif,
+    ];
+
+    pub fn from_index(index: u32) -> OutputFormat {
+        OutputFormat::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        OutputFormat::ALL.iter().position(|format| *format == self).unwrap_or_default() as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Keep => "Keep original",
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Png => "PNG",
+            OutputFormat::WebP => "WebP",
+            OutputFormat::Avif => "AVIF",
+        }
+    }
+
+    // None keeps the source format
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            OutputFormat::Keep => None,
+            OutputFormat::Jpeg => Some(ImageFormat::Jpeg),
+            OutputFormat::Png => Some(ImageFormat::Png),
+            OutputFormat::WebP => Some(ImageFormat::WebP),
+            OutputFormat::Avif => Some(ImageFormat::Avif),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngCompression {
+    #[default]
+    Fast,
+    Balanced,
+    Smallest,
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] = [
+        PngCompression::Fast,
+        PngCompression::Balanced,
+        PngCompression::Smallest,
+    ];
+
+    pub fn from_index(index: u32) -> PngCompression {
+        PngCompression::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        PngCompression::ALL.iter().position(|compression| *compression == self).unwrap_or_default() as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PngCompression::Fast => "Fast",
+            PngCompression::Balanced => "Balanced",
+            PngCompression::Smallest => "Smallest",
+        }
+    }
+
+    pub(crate) fn compression_type(self) -> CompressionType {
+        match self {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Balanced => CompressionType::Default,
+            PngCompression::Smallest => CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputSettings {
+    pub format:             OutputFormat,
+    // 1 (smallest) to 100 (best), for JPEG, AVIF and lossy WebP
+    pub quality:            u8,
+    pub png_compression:    PngCompression,
+    // lossy WebPs are encoded with `quality`
+    pub webp_lossless:      bool,
+    // straight RGB that transparent pixels are flattened onto for formats without alpha
+    pub background:         [u8; 3],
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            format: OutputFormat::Keep,
+            quality: 90,
+            png_compression: PngCompression::Fast,
+            webp_lossless: true,
+            background: [255, 255, 255],
+        }
+    }
+}
+
+// formats that can store transparency
+pub fn has_alpha_support(format: ImageFormat) -> bool {
+    !matches!(format, ImageFormat::Jpeg)
+}
+
+// composites the image onto an opaque `background`, images without alpha are returned unchanged
+pub fn flatten(image: DynamicImage, background: [u8; 3]) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return image;
+    }
+
+    let image = image.into_rgba8();
+    let flattened = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y);
+        let alpha = pixel[3] as u32;
+        Rgb([0, 1, 2].map(|channel| ((pixel[channel] as u32 * alpha + background[channel] as u32 * (255 - alpha) + 127) / 255) as u8))
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
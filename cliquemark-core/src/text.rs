@@ -0,0 +1,194 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use ab_glyph::{
+    point,
+    Font,
+    FontVec,
+    Glyph,
+    PxScale,
+    ScaleFont,
+    VariableFont,
+};
+use image::{Rgba, RgbaImage};
+
+use crate::WatermarkError;
+
+// font size the text is measured at to get its natural aspect ratio
+const REFERENCE_PX_SIZE: f32 = 100.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontSource {
+    // family name of an installed font, e.g. "DejaVu Sans"
+    System(String),
+    // TTF or OTF file
+    File(PathBuf),
+}
+
+impl Default for FontSource {
+    fn default() -> Self {
+        FontSource::System("Sans".to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextWatermark {
+    pub text:   String,
+    pub font:   FontSource,
+    // straight (non premultiplied) RGBA
+    pub color:  [u8; 4],
+    // CSS style weight, 100 (thin) to 900 (black)
+    pub weight: u16,
+}
+
+impl TextWatermark {
+    // loads the font once so the text can be rendered for every image of a batch
+    pub fn renderer(&self) -> Result<TextRenderer, WatermarkError> {
+        if self.text.trim().is_empty() {
+            return Err(WatermarkError::EmptyText);
+        }
+
+        let mut font = match &self.font {
+            FontSource::File(path) => {
+                FontVec::try_from_vec(fs::read(path)?)
+                    .map_err(|_| WatermarkError::Font(format!("{} is not a valid font file", path.display())))?
+            }
+            FontSource::System(family) => load_system_font(family, self.weight)?,
+        };
+        // only does something for variable fonts, static faces keep their own weight
+        font.set_variation(b"wght", self.weight as f32);
+
+        Ok(TextRenderer {
+            text: self.text.clone(),
+            font,
+            color: self.color,
+        })
+    }
+}
+
+fn system_fonts() -> &'static fontdb::Database {
+    static SYSTEM_FONTS: OnceLock<fontdb::Database> = OnceLock::new();
+
+    SYSTEM_FONTS.get_or_init(|| {
+        let mut database = fontdb::Database::new();
+        database.load_system_fonts();
+        database
+    })
+}
+
+// family names of the installed fonts, sorted and without duplicates
+pub fn system_font_families() -> Vec<String> {
+    let mut families = system_fonts()
+        .faces()
+        .filter_map(|face| face.families.first().map(|(family, _)| family.clone()))
+        .collect::<Vec<_>>();
+    families.sort();
+    families.dedup();
+    families
+}
+
+fn load_system_font(family: &str, weight: u16) -> Result<FontVec, WatermarkError> {
+    let database = system_fonts();
+    let query = fontdb::Query {
+        families: &[fontdb::Family::Name(family), fontdb::Family::SansSerif],
+        weight: fontdb::Weight(weight),
+        ..fontdb::Query::default()
+    };
+
+    // generic names like "Sans" are fontconfig aliases, fall back to any installed sans serif family
+    let fallback_query = || {
+        let fallback_family = system_font_families()
+            .into_iter()
+            .find(|family| family.contains("Sans"))
+            .or_else(|| system_font_families().into_iter().next())?;
+
+        database.query(&fontdb::Query {
+            families: &[fontdb::Family::Name(&fallback_family)],
+            ..query
+        })
+    };
+
+    database
+        .query(&query)
+        .or_else(fallback_query)
+        .and_then(|id| database.with_face_data(id, |data, index| FontVec::try_from_vec_and_index(data.to_vec(), index).ok()))
+        .flatten()
+        .ok_or_else(|| WatermarkError::Font(format!("No usable font found for \"{}\"", family)))
+}
+
+pub struct TextRenderer {
+    text:   String,
+    font:   FontVec,
+    color:  [u8; 4],
+}
+
+impl TextRenderer {
+    // glyphs laid out at `px_size` and the size of the box around them
+    fn layout(&self, px_size: f32) -> (Vec<Glyph>, [f32; 2]) {
+        let font = self.font.as_scaled(PxScale::from(px_size));
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let mut width: f32 = 0.0;
+        let mut line_count = 0;
+
+        for (line_index, line) in self.text.lines().enumerate() {
+            let mut caret = point(0.0, font.ascent() + line_index as f32 * line_height);
+            let mut previous_glyph = None;
+
+            for character in line.chars() {
+                let glyph_id = font.glyph_id(character);
+                if let Some(previous_glyph) = previous_glyph {
+                    caret.x += font.kern(previous_glyph, glyph_id);
+                }
+                glyphs.push(glyph_id.with_scale_and_position(px_size, caret));
+                caret.x += font.h_advance(glyph_id);
+                previous_glyph = Some(glyph_id);
+            }
+            width = width.max(caret.x);
+            line_count += 1;
+        }
+
+        let height = font.height() + (line_count.max(1) - 1) as f32 * line_height;
+        (glyphs, [width, height])
+    }
+
+    // natural size of the text, only its aspect ratio is meaningful
+    pub fn dimensions(&self) -> [u32; 2] {
+        let (_, [width, height]) = self.layout(REFERENCE_PX_SIZE);
+        [width.ceil().max(1.0) as u32, height.ceil().max(1.0) as u32]
+    }
+
+    // rasterises the text straight into a `size` canvas so it stays sharp at any resolution
+    pub fn render(&self, size: [u32; 2]) -> RgbaImage {
+        let [red, green, blue, alpha] = self.color;
+        let mut canvas = RgbaImage::from_pixel(size[0], size[1], Rgba([red, green, blue, 0]));
+
+        let (_, [_, reference_height]) = self.layout(REFERENCE_PX_SIZE);
+        let px_size = REFERENCE_PX_SIZE * size[1] as f32 / reference_height;
+        let (glyphs, _) = self.layout(px_size);
+
+        for glyph in glyphs {
+            let Some(outline) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+
+            outline.draw(|x, y, coverage| {
+                let pixel_x = bounds.min.x as i64 + x as i64;
+                let pixel_y = bounds.min.y as i64 + y as i64;
+                if pixel_x < 0 || pixel_y < 0 || pixel_x >= size[0] as i64 || pixel_y >= size[1] as i64 {
+                    return;
+                }
+
+                let pixel = canvas.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+                let pixel_alpha = (coverage.min(1.0) * alpha as f32).round() as u8;
+                pixel[3] = pixel[3].max(pixel_alpha);
+            });
+        }
+        canvas
+    }
+}
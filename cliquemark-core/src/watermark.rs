@@ -0,0 +1,595 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image::{
+    imageops::{self, FilterType::{Lanczos3, Triangle}},
+    DynamicImage,
+    ImageFormat,
+    Rgba,
+    RgbaImage,
+};
+use rayon::prelude::*;
+
+use crate::{
+    blend_overlay,
+    flatten,
+    has_alpha_support,
+    load_image,
+    load_image_with_metadata,
+    save_image,
+    BatchControl,
+    BlendMode,
+    Geometry,
+    NameTemplate,
+    OutputSettings,
+    TextRenderer,
+    WatermarkError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    #[default]
+    BottomRight,
+}
+
+impl Anchor {
+    // row by row, the same order as the anchor buttons in the settings sidebar
+    pub const ALL: [Anchor; 9] = [
+        Anchor::TopLeft,
+        Anchor::TopCenter,
+        Anchor::TopRight,
+        Anchor::CenterLeft,
+        Anchor::Center,
+        Anchor::CenterRight,
+        Anchor::BottomLeft,
+        Anchor::BottomCenter,
+        Anchor::BottomRight,
+    ];
+
+    pub fn from_index(index: u32) -> Anchor {
+        Anchor::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        Anchor::ALL.iter().position(|anchor| *anchor == self).unwrap_or_default() as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Anchor::TopLeft => "Top left",
+            Anchor::TopCenter => "Top",
+            Anchor::TopRight => "Top right",
+            Anchor::CenterLeft => "Left",
+            Anchor::Center => "Center",
+            Anchor::CenterRight => "Right",
+            Anchor::BottomLeft => "Bottom left",
+            Anchor::BottomCenter => "Bottom",
+            Anchor::BottomRight => "Bottom right",
+        }
+    }
+
+    // top left position of a `size` box anchored in `canvas`, kept `margin` away from the edges
+    // it is anchored to and moved by `offset` afterwards
+    pub fn position(self, canvas: [i64; 2], size: [i64; 2], margin: i64, offset: [i64; 2]) -> [i64; 2] {
+        let align = |alignment: u32, canvas: i64, size: i64| match alignment {
+            0 => margin,
+            1 => (canvas - size) / 2,
+            _ => canvas - size - margin,
+        };
+
+        [
+            align(self.index() % 3, canvas[0], size[0]) + offset[0],
+            align(self.index() / 3, canvas[1], size[1]) + offset[1],
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tiling {
+    // gaps between the tiles as a fraction of the image width
+    pub horizontal_spacing: f32,
+    pub vertical_spacing:   f32,
+    // shift of every other row as a fraction of the horizontal tile step, 0.5 gives a brick pattern
+    pub row_offset:         f32,
+}
+
+// top left positions of `size` tiles repeated over `canvas`, the unshifted rows pass through `origin`
+pub fn tile_positions(
+    canvas:     [i64; 2],
+    size:       [i64; 2],
+    spacing:    [i64; 2],
+    row_offset: f32,
+    origin:     [i64; 2],
+) -> Vec<[i64; 2]>
+{
+    let step = [(size[0] + spacing[0]).max(1), (size[1] + spacing[1]).max(1)];
+    let row_shift = (row_offset * step[0] as f32).round() as i64;
+    let mut positions = Vec::new();
+
+    let mut y = origin[1].rem_euclid(step[1]) - step[1];
+    while y < canvas[1] {
+        let row_index = (y - origin[1]).div_euclid(step[1]);
+        let shift = if row_index.rem_euclid(2) == 1 { row_shift } else { 0 };
+
+        let mut x = (origin[0] + shift).rem_euclid(step[0]) - step[0];
+        while x < canvas[0] {
+            if x + size[0] > 0 && y + size[1] > 0 {
+                positions.push([x, y]);
+            }
+            x += step[0];
+        }
+        y += step[1];
+    }
+    positions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatermarkSettings {
+    // watermark area as a fraction of the image area
+    pub relative_surface_area:  f32,
+    // margin as a fraction of the image width
+    pub relative_margin_width:  f32,
+    pub anchor:                 Anchor,
+    // signed shift after anchoring, as fractions of the image width and height
+    pub relative_offset:        [f32; 2],
+    // clockwise, in degrees, the anchor and tiles place the box around the rotated watermark
+    pub rotation:               f32,
+    // repeats the watermark over the whole image instead of anchoring it,
+    // the margin and offset then move the first tile
+    pub tiling:                 Option<Tiling>,
+    // 0.0 is fully transparent, 1.0 keeps the watermark's own alpha
+    pub opacity:                f32,
+    pub blend_mode:             BlendMode,
+}
+
+impl WatermarkSettings {
+    // settings that reproduce the preview placement on a reference image of `image_dimensions` pixels,
+    // `margin` is in pixels of that reference image
+    pub fn from_scale(
+        scale:                  f64,
+        margin:                 f64,
+        image_dimensions:       [u32; 2],
+        watermark_dimensions:   [u32; 2],
+        anchor:                 Anchor,
+    ) -> WatermarkSettings
+    {
+        let [_, _, width, height] = preview_watermark_rectangle(
+            [image_dimensions[0] as i32, image_dimensions[1] as i32],
+            [watermark_dimensions[0] as i32, watermark_dimensions[1] as i32],
+            [image_dimensions[0] as i32, image_dimensions[1] as i32],
+            scale,
+            0,
+            anchor,
+            [0.0, 0.0],
+            0.0,
+        );
+        let image_area = image_dimensions[0] as f32 * image_dimensions[1] as f32;
+
+        WatermarkSettings {
+            relative_surface_area: width as f32 * height as f32 / image_area,
+            relative_margin_width: margin as f32 / image_dimensions[0] as f32,
+            anchor,
+            relative_offset: [0.0, 0.0],
+            rotation: 0.0,
+            tiling: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+pub enum WatermarkSource {
+    Image(DynamicImage),
+    Text(TextRenderer),
+}
+
+impl WatermarkSource {
+    pub fn dimensions(&self) -> [u32; 2] {
+        match self {
+            WatermarkSource::Image(image) => [image.width(), image.height()],
+            WatermarkSource::Text(text) => text.dimensions(),
+        }
+    }
+
+    // the watermark at exactly `size` pixels, text is rasterised at that size instead of resampled
+    pub fn render(&self, size: [u32; 2]) -> RgbaImage {
+        match self {
+            WatermarkSource::Image(image) => imageops::resize(image, size[0], size[1], Triangle),
+            WatermarkSource::Text(text) => text.render(size),
+        }
+    }
+}
+
+pub struct WatermarkJob {
+    pub image_entries:  Vec<PathBuf>,
+    // the images keep their path relative to this folder inside `target_folder`
+    pub source_folder:  PathBuf,
+    pub watermark:      WatermarkSource,
+    pub target_folder:  PathBuf,
+    // applied before watermarking, so the relative settings are measured on the final image
+    pub geometry:       Geometry,
+    pub settings:       WatermarkSettings,
+    pub output:         OutputSettings,
+    // None keeps the file names and the folder structure of `source_folder`
+    pub name_template:  Option<NameTemplate>,
+}
+
+#[derive(Debug)]
+pub struct WatermarkResult {
+    pub source: PathBuf,
+    // the planned output path, None when the image didn't get one
+    pub target: Option<PathBuf>,
+    pub result: Result<(), WatermarkError>,
+}
+
+impl WatermarkResult {
+    // the image was left alone rather than failing, because its output name was already taken
+    // or the batch was cancelled
+    pub fn is_skipped(&self) -> bool {
+        matches!(self.result, Err(WatermarkError::NameCollision { .. } | WatermarkError::Cancelled))
+    }
+}
+
+pub enum Progress<'a> {
+    Started { total: usize },
+    Processed { completed: usize, total: usize, result: &'a WatermarkResult },
+}
+
+// size of the box around a `size` watermark rotated by `rotation` degrees
+pub fn rotated_dimensions(size: [f64; 2], rotation: f32) -> [f64; 2] {
+    let (sin, cos) = (rotation as f64).to_radians().sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+
+    [size[0] * cos + size[1] * sin, size[0] * sin + size[1] * cos]
+}
+
+// bilinear sample at `[x, y]` with premultiplied alpha, pixels outside the image are transparent
+fn sample_premultiplied(image: &RgbaImage, x: f64, y: f64) -> [f64; 4] {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut sample = [0.0; 4];
+
+    for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+        let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+        if weight == 0.0 || px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+            continue;
+        }
+
+        let pixel = image.get_pixel(px as u32, py as u32);
+        let alpha = pixel[3] as f64 / 255.0;
+        for channel in 0..3 {
+            sample[channel] += pixel[channel] as f64 * alpha * weight;
+        }
+        sample[3] += alpha * weight;
+    }
+    sample
+}
+
+// rotates clockwise around the centre onto a canvas grown to the rotated bounding box,
+// interpolating premultiplied colours so transparent pixels don't leave dark fringes
+pub fn rotate_watermark(watermark: &RgbaImage, rotation: f32) -> RgbaImage {
+    let rotation = rotation.rem_euclid(360.0);
+
+    // right angles turn without resampling
+    if rotation == 0.0 {
+        return watermark.clone();
+    } else if rotation == 90.0 {
+        return imageops::rotate90(watermark);
+    } else if rotation == 180.0 {
+        return imageops::rotate180(watermark);
+    } else if rotation == 270.0 {
+        return imageops::rotate270(watermark);
+    }
+
+    let (width, height) = (watermark.width() as f64, watermark.height() as f64);
+    let [rotated_width, rotated_height] = rotated_dimensions([width, height], rotation).map(|length| length.ceil().max(1.0) as u32);
+    let (sin, cos) = (rotation as f64).to_radians().sin_cos();
+
+    RgbaImage::from_fn(rotated_width, rotated_height, |x, y| {
+        let dx = x as f64 + 0.5 - rotated_width as f64 / 2.0;
+        let dy = y as f64 + 0.5 - rotated_height as f64 / 2.0;
+        // pixel of the unrotated watermark that lands here
+        let source_x = cos * dx + sin * dy + width / 2.0 - 0.5;
+        let source_y = -sin * dx + cos * dy + height / 2.0 - 0.5;
+
+        let [red, green, blue, alpha] = sample_premultiplied(watermark, source_x, source_y);
+        if alpha <= 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        Rgba([
+            (red / alpha).round().min(255.0) as u8,
+            (green / alpha).round().min(255.0) as u8,
+            (blue / alpha).round().min(255.0) as u8,
+            (alpha * 255.0).round().min(255.0) as u8,
+        ])
+    })
+}
+
+// rectangle `[x, y, width, height]` of the watermark on a preview of `display_dimensions` pixels,
+// `scale` is the fraction of the image's short side the unrotated watermark spans
+#[allow(clippy::too_many_arguments)]
+pub fn preview_watermark_rectangle(
+    image_dimensions:       [i32; 2],
+    watermark_dimensions:   [i32; 2],
+    display_dimensions:     [i32; 2],
+    scale:                  f64,
+    margin:                 i32,
+    anchor:                 Anchor,
+    relative_offset:        [f32; 2],
+    rotation:               f32,
+) -> [i32; 4]
+{
+    if image_dimensions[0] == 0 || image_dimensions[1] == 0 {
+        return [0, 0, 0, 0];
+    }
+
+    let width_ratio = watermark_dimensions[0] as f64 / image_dimensions[0] as f64;
+    let height_ratio = watermark_dimensions[1] as f64 / image_dimensions[1] as f64;
+    let global_scale = display_dimensions[0] as f32 / image_dimensions[0] as f32;
+
+    let range_correction: f64 = if image_dimensions[0] <= image_dimensions[1] { 1.0 / width_ratio } else { 1.0 / height_ratio };
+
+    let width = (width_ratio * display_dimensions[0] as f64 * scale * range_correction).ceil();
+    let height = (height_ratio * display_dimensions[1] as f64 * scale * range_correction).ceil();
+    let [width, height] = rotated_dimensions([width, height], rotation).map(|length| length.round() as i32);
+
+    let adjusted_margin = (margin as f32 * global_scale).ceil() as i32;
+
+    let [x, y] = anchor.position(
+        [display_dimensions[0] as i64, display_dimensions[1] as i64],
+        [width as i64, height as i64],
+        adjusted_margin as i64,
+        [
+            (relative_offset[0] * display_dimensions[0] as f32).round() as i64,
+            (relative_offset[1] * display_dimensions[1] as f32).round() as i64,
+        ],
+    );
+    [x as i32, y as i32, width, height]
+}
+
+pub fn apply_opacity(watermark: &mut RgbaImage, opacity: f32) {
+    if opacity >= 1.0 {
+        return;
+    }
+    let opacity = opacity.max(0.0);
+
+    for pixel in watermark.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+}
+
+pub fn watermark_image(
+    image:      &mut DynamicImage,
+    watermark:  &WatermarkSource,
+    settings:   &WatermarkSettings,
+) {
+    let [watermark_width, watermark_height] = watermark.dimensions();
+    let watermark_surface_area = settings.relative_surface_area * image.width() as f32 * image.height() as f32;
+    let watermark_aspect_ratio = watermark_width as f32 / watermark_height as f32;
+
+    let watermark_scaled_width = (watermark_surface_area * watermark_aspect_ratio).sqrt().round() as i64;
+    let watermark_scaled_height = (watermark_surface_area / watermark_aspect_ratio).sqrt().round() as i64;
+
+    if watermark_scaled_width <= 0 || watermark_scaled_height <= 0 {
+        return;
+    }
+
+    let margin_scaled = (settings.relative_margin_width * image.width() as f32).round() as i64;
+    let offset_scaled = [
+        (settings.relative_offset[0] * image.width() as f32).round() as i64,
+        (settings.relative_offset[1] * image.height() as f32).round() as i64,
+    ];
+
+    let mut watermark_scaled = watermark.render([watermark_scaled_width as u32, watermark_scaled_height as u32]);
+    apply_opacity(&mut watermark_scaled, settings.opacity);
+    let watermark_scaled = rotate_watermark(&watermark_scaled, settings.rotation);
+
+    let canvas = [image.width() as i64, image.height() as i64];
+    let size = [watermark_scaled.width() as i64, watermark_scaled.height() as i64];
+
+    let positions = match settings.tiling {
+        Some(tiling) => tile_positions(
+            canvas,
+            size,
+            [
+                (tiling.horizontal_spacing * image.width() as f32).round() as i64,
+                (tiling.vertical_spacing * image.width() as f32).round() as i64,
+            ],
+            tiling.row_offset,
+            [margin_scaled + offset_scaled[0], margin_scaled + offset_scaled[1]],
+        ),
+        None => vec![settings.anchor.position(canvas, size, margin_scaled, offset_scaled)],
+    };
+
+    for [watermark_position_x, watermark_position_y] in positions {
+        blend_overlay(image, &watermark_scaled, watermark_position_x, watermark_position_y, settings.blend_mode);
+    }
+}
+
+// only the watermark on a transparent `canvas`, for previews
+pub fn watermark_layer(
+    canvas:     [u32; 2],
+    watermark:  &WatermarkSource,
+    settings:   &WatermarkSettings,
+) -> RgbaImage
+{
+    let mut layer = DynamicImage::ImageRgba8(RgbaImage::new(canvas[0], canvas[1]));
+    watermark_image(&mut layer, watermark, settings);
+    layer.into_rgba8()
+}
+
+fn process_image(image_entry: &Path, target_path: &Path, job: &WatermarkJob, control: &BatchControl) -> Result<(), WatermarkError> {
+    if !control.wait_while_paused() {
+        return Err(WatermarkError::Cancelled);
+    }
+
+    let (image, metadata) = load_image_with_metadata(image_entry)?;
+    let target_format = ImageFormat::from_path(target_path)?;
+    let image = render_image(image, &job.geometry, &job.watermark, &job.settings, &job.output, Some(target_format));
+
+    if let Some(target_parent) = target_path.parent() {
+        fs::create_dir_all(target_parent)?;
+    }
+    if control.is_cancelled() {
+        return Err(WatermarkError::Cancelled);
+    }
+    save_image(&image, target_path, &metadata, &job.output)
+}
+
+// the pixels an image is saved with, `target_format` decides whether its transparency is flattened
+fn render_image(
+    image:          DynamicImage,
+    geometry:       &Geometry,
+    watermark:      &WatermarkSource,
+    settings:       &WatermarkSettings,
+    output:         &OutputSettings,
+    target_format:  Option<ImageFormat>,
+) -> DynamicImage {
+    let mut image = geometry.apply(image);
+    watermark_image(&mut image, watermark, settings);
+
+    match target_format {
+        Some(target_format) if !has_alpha_support(target_format) => flatten(image, output.background),
+        _ => image,
+    }
+}
+
+// `image_entry` the way the export would save it, scaled down to fit `max_dimensions` for showing it on screen,
+// it's rendered at full size first so the watermark is placed and filtered exactly like in the saved image
+pub fn render_preview(
+    image_entry:    &Path,
+    geometry:       &Geometry,
+    watermark:      &WatermarkSource,
+    settings:       &WatermarkSettings,
+    output:         &OutputSettings,
+    max_dimensions: [u32; 2],
+) -> Result<RgbaImage, WatermarkError> {
+    let image = load_image(image_entry)?;
+    let target_format = output.format.image_format().or_else(|| ImageFormat::from_path(image_entry).ok());
+    let image = render_image(image, geometry, watermark, settings, output, target_format);
+
+    let [max_width, max_height] = max_dimensions.map(|length| length.max(1));
+    if image.width() <= max_width && image.height() <= max_height {
+        return Ok(image.into_rgba8());
+    }
+    Ok(image.resize(max_width, max_height, Lanczos3).into_rgba8())
+}
+
+// watermarks each source image of `targets` to its planned output path
+fn watermark_targets<F>(
+    job:        &WatermarkJob,
+    targets:    Vec<(PathBuf, Result<PathBuf, WatermarkError>)>,
+    control:    &BatchControl,
+    progress:   F,
+) -> Vec<WatermarkResult>
+where
+    F: Fn(Progress) + Sync,
+{
+    let total = targets.len();
+    let completed = AtomicUsize::new(0);
+    progress(Progress::Started { total });
+
+    targets.into_par_iter().map(|(image_entry, target_path)| {
+        let result = match target_path {
+            Ok(target_path) => WatermarkResult {
+                result: process_image(&image_entry, &target_path, job, control),
+                source: image_entry,
+                target: Some(target_path),
+            },
+            Err(error) => WatermarkResult { source: image_entry, target: None, result: Err(error) },
+        };
+
+        let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+        progress(Progress::Processed { completed, total, result: &result });
+        result
+    }).collect()
+}
+
+// watermarks every image of the job in parallel to the paths `target_paths` planned for it, `progress` is called
+// from the worker threads, images whose output name was already taken by another image of the batch are skipped
+// instead of overwriting it
+pub fn apply_watermark<F>(
+    job:            &WatermarkJob,
+    target_paths:   Vec<Result<PathBuf, WatermarkError>>,
+    control:        &BatchControl,
+    progress:       F,
+) -> Vec<WatermarkResult>
+where
+    F: Fn(Progress) + Sync,
+{
+    debug_assert_eq!(job.image_entries.len(), target_paths.len(), "every image needs a planned target path");
+    // an image without a planned path fails instead of being dropped from the results
+    let mut target_paths = target_paths.into_iter();
+    let targets = job.image_entries
+        .iter()
+        .map(|image_entry| {
+            let target_path = target_paths.next().unwrap_or_else(|| Err(WatermarkError::InvalidFileName(image_entry.clone())));
+            (image_entry.clone(), target_path)
+        })
+        .collect();
+    watermark_targets(job, targets, control, progress)
+}
+
+// runs the job again for only the source images of `retries`, e.g. the ones that failed, they are written to the
+// `target` of their earlier result so they keep the name and counter of the first run
+pub fn retry_watermark<F>(job: &WatermarkJob, retries: &[(PathBuf, PathBuf)], control: &BatchControl, progress: F) -> Vec<WatermarkResult>
+where
+    F: Fn(Progress) + Sync,
+{
+    let targets = retries.iter().map(|(source, target)| (source.clone(), Ok(target.clone()))).collect();
+    watermark_targets(job, targets, control, progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_keep_the_margin_to_their_edges() {
+        let position = |anchor: Anchor, offset| anchor.position([100, 50], [10, 10], 5, offset);
+
+        assert_eq!(position(Anchor::TopLeft, [0, 0]), [5, 5]);
+        assert_eq!(position(Anchor::TopCenter, [0, 0]), [45, 5]);
+        assert_eq!(position(Anchor::Center, [0, 0]), [45, 20]);
+        assert_eq!(position(Anchor::CenterRight, [0, 0]), [85, 20]);
+        assert_eq!(position(Anchor::BottomRight, [0, 0]), [85, 35]);
+        // the offset moves the anchored position
+        assert_eq!(position(Anchor::BottomRight, [-3, 2]), [82, 37]);
+    }
+
+    #[test]
+    fn anchor_indices_round_trip() {
+        for anchor in Anchor::ALL {
+            assert_eq!(Anchor::from_index(anchor.index()), anchor);
+        }
+        assert_eq!(Anchor::from_index(9), Anchor::default());
+    }
+
+    #[test]
+    fn tiles_cover_the_canvas() {
+        let positions = tile_positions([10, 10], [4, 4], [1, 1], 0.0, [0, 0]);
+
+        assert_eq!(positions, [[0, 0], [5, 0], [0, 5], [5, 5]]);
+    }
+
+    #[test]
+    fn shifts_every_other_row_for_bricks() {
+        let positions = tile_positions([10, 10], [4, 4], [1, 1], 0.5, [0, 0]);
+        let row = |y: i64| positions.iter().filter(|position| position[1] == y).map(|position| position[0]).collect::<Vec<_>>();
+
+        assert_eq!(row(0), [0, 5]);
+        // half of the 5 pixel step, rounded
+        assert_eq!(row(5), [-2, 3, 8]);
+    }
+}
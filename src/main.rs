@@ -9,7 +9,12 @@ use adw::{
     glib,
     ApplicationWindow, 
     gdk::{
+        DragAction,
+        FileList,
+        MemoryFormat,
+        MemoryTexture,
         Rectangle,
+        RGBA,
         Texture,
     },
     ToggleGroup,
@@ -19,8 +24,12 @@ use adw::{
     OverlaySplitView,
     PreferencesGroup,
     ActionRow,
+    Clamp,
+    ComboRow,
+    EntryRow,
     Spinner,
     SpinRow,
+    SwitchRow,
     ToastOverlay,
     Toast
 };
@@ -30,47 +39,124 @@ use gtk::{
     Box, 
     Orientation, 
     Button,
+    CheckButton,
+    DropTarget,
+    ToggleButton,
+    ColorDialog,
+    ColorDialogButton,
+    FileFilter,
+    FontDialog,
+    FontDialogButton,
+    FontLevel,
     Scale,
     Adjustment,
     FileDialog,
     Grid,
+    Label,
+    ListItem,
+    ListScrollFlags,
+    ListView,
+    MenuButton,
     Overlay,
     Picture,
-    gdk_pixbuf::Pixbuf,
+    gdk_pixbuf::{Colorspace, InterpType, Pixbuf},
+    SignalListItemFactory,
+    SingleSelection,
+    StringList,
+    pango::FontDescription,
     Stack,
     StackTransitionType,
     Entry,
     EntryBuffer,
+    PolicyType,
     PositionType,
     ProgressBar,
+    ScrolledWindow,
     gio,
     };
 
 use std::{
-    cell::RefCell, 
-    fs, 
-    io, 
-    path::PathBuf, 
+    cell::{Cell, RefCell}, 
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf}, 
     rc::Rc, 
-    char,
+    sync::Arc,
     env
 };
 
-use rayon::prelude::*;
-
-use image::{
-    imageops::{self, FilterType::Triangle}, 
-    metadata::Orientation as ImageOrientation, 
-    DynamicImage, 
-    ImageDecoder, 
-    ImageReader
-    };
-use rand::prelude::IndexedRandom;
+use cliquemark_core::{
+    apply_watermark,
+    collect_input_files,
+    common_folder,
+    create_target_folder,
+    TARGET_FOLDER_NAME,
+    is_image_file,
+    load_image,
+    preview_watermark_rectangle,
+    render_preview,
+    retry_watermark,
+    rotate_watermark,
+    target_paths,
+    watermark_image,
+    Anchor,
+    BatchControl,
+    BlendMode,
+    OutputFormat,
+    OutputSettings,
+    PngCompression,
+    FontSource,
+    Geometry,
+    NameTemplate,
+    NAME_TOKENS,
+    Progress,
+    Reframe,
+    Resize,
+    TextWatermark,
+    watermark_layer,
+    Tiling,
+    WatermarkError,
+    WatermarkJob,
+    WatermarkResult,
+    WatermarkSettings,
+    WatermarkSource,
+};
+use cliquemark_core::image::{DynamicImage, Rgba, RgbaImage};
+
+mod presets;
+use presets::{
+    load_presets,
+    presets_path,
+    PresetList,
+    PresetRows,
+};
+mod viewport;
+use viewport::{
+    PreviewComparison,
+    PreviewViewport,
+    WatermarkDragging,
+};
 
 
 
 const APP_ID: &str = "org.gtk_rs.Cliquemark"; 
 
+// longest side of the preview image copy the export engine renders on, the picture scales it to the preview size
+const RENDERED_PREVIEW_SIZE: f32 = 1024.0;
+// longest side a rotated image watermark is rendered at for the preview
+const ROTATED_PREVIEW_SIZE: f32 = 1024.0;
+// longest side of the filmstrip thumbnails
+const THUMBNAIL_SIZE: i32 = 72;
+const PREVIEW_STYLE: &str = "
+.watermark-handle { background-color: @accent_bg_color; border: 1px solid white; border-radius: 50%; }
+.snap-guide { background-color: @accent_bg_color; }
+";
+// width:height ratios offered for cropping and padding
+const ASPECT_RATIOS: [[u32; 2]; 9] = [[1, 1], [4, 5], [5, 4], [2, 3], [3, 2], [3, 4], [4, 3], [9, 16], [16, 9]];
+// folder of the repository with the GSettings schemas, installers copy its compiled schemas
+#[cfg(debug_assertions)]
+const SCHEMA_FOLDER_NAME: &str = "schema_files";
+
 fn main() -> glib::ExitCode {
     if cfg!(target_os = "macos") {
         if let Ok(exe_path) = env::current_exe() {
@@ -95,42 +181,635 @@ fn main() -> glib::ExitCode {
     return app.run();
 }
 
+// the installed schema, without it the app starts with its defaults and remembers nothing
+fn load_settings() -> Option<gio::Settings> {
+    let default_source = gio::SettingsSchemaSource::default();
+    let schema = default_source
+        .as_ref()
+        .and_then(|schema_source| schema_source.lookup(APP_ID, true));
+
+    // development only, debug builds run with cargo also find the uninstalled schema of the repository,
+    // release builds never look in the folder they were compiled in
+    #[cfg(debug_assertions)]
+    let schema = schema.or_else(|| {
+        let schema_folder = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_FOLDER_NAME);
+        gio::SettingsSchemaSource::from_directory(schema_folder, default_source.as_ref(), false)
+            .ok()?
+            .lookup(APP_ID, false)
+    });
+
+    match schema {
+        Some(schema) => Some(gio::Settings::new_full(&schema, None::<&gio::SettingsBackend>, None)),
+        None => {
+            eprintln!("Settings schema {} not found, settings won't be remembered", APP_ID);
+            None
+        }
+    }
+}
+
+// remembers the chosen folders and files for the next launch
+fn save_last_inputs(settings: Option<&gio::Settings>, inputs: &[PathBuf]) {
+    if let Some(settings) = settings {
+        let last_inputs: Vec<String> = inputs.iter().map(|input| input.to_string_lossy().to_string()).collect();
+        let _ = settings.set_strv("last-inputs", last_inputs);
+    }
+}
+
+// value of the anchor in the settings, e.g. "bottom-right"
+fn anchor_setting(anchor: Anchor) -> String {
+    anchor.name().to_lowercase().replace(' ', "-")
+}
+
+#[allow(clippy::too_many_arguments)]
 fn calculate_watermark_position(
     preview_image_dimensions:       &RefCell<[i32; 2]>,
     preview_watermark_dimensions:   &RefCell<[i32; 2]>,
     image_preview:                  &Rc<Picture>,
     scale_slider_value:             &f64,
     margin_value:                   i32,
-    active_alignment_array:         [i32; 4],
+    anchor:                         Anchor,
+    relative_offset:                [f32; 2],
+    rotation:                       f32,
 ) -> Rectangle 
 {
-    let mut width_ratio = 0.0;
-    let mut height_ratio = 0.0;
-    let mut global_scale = 1.0;
-    
-    if preview_image_dimensions.borrow()[0] != 0 && preview_image_dimensions.borrow()[1] != 0 {
-        width_ratio = preview_watermark_dimensions.borrow()[0] as f64 / preview_image_dimensions.borrow()[0] as f64;
-        height_ratio = preview_watermark_dimensions.borrow()[1] as f64 / preview_image_dimensions.borrow()[1] as f64;
-    
-        global_scale = image_preview.width() as f32 / preview_image_dimensions.borrow()[0] as f32;
+    let [x, y, width, height] = preview_watermark_rectangle(
+        *preview_image_dimensions.borrow(),
+        *preview_watermark_dimensions.borrow(),
+        [image_preview.width(), image_preview.height()],
+        *scale_slider_value,
+        margin_value,
+        anchor,
+        relative_offset,
+        rotation,
+    );
+
+    return Rectangle::new(x, y, width, height);
+}
+
+
+// rows of the text watermark settings, cloning only clones the widget references
+#[derive(Clone)]
+struct TextWatermarkRows {
+    text_entry_row:     EntryRow,
+    font_action_row:    ActionRow,
+    font_source:        Rc<RefCell<FontSource>>,
+    color_button:       ColorDialogButton,
+    weight_spin_row:    SpinRow,
+}
+
+impl TextWatermarkRows {
+    fn text_watermark(&self) -> TextWatermark {
+        let color = self.color_button.rgba();
+
+        TextWatermark {
+            text: self.text_entry_row.text().to_string(),
+            font: self.font_source.borrow().clone(),
+            color: [color.red(), color.green(), color.blue(), color.alpha()].map(|channel| (channel * 255.0).round() as u8),
+            weight: self.weight_spin_row.value() as u16,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PositionRows {
+    anchor_action_row:          ActionRow,
+    // row by row, in the order of `Anchor::ALL`
+    anchor_buttons:             Vec<ToggleButton>,
+    offset_x_spin_row:          SpinRow,
+    offset_y_spin_row:          SpinRow,
+    offset_unit_toggle_group:   ToggleGroup,
+}
+
+impl PositionRows {
+    fn anchor(&self) -> Anchor {
+        let active_index = self.anchor_buttons.iter().position(|anchor_button| anchor_button.is_active());
+        active_index.map(|index| Anchor::from_index(index as u32)).unwrap_or_default()
+    }
+
+    // offsets are entered in pixels of the preview image or in percent of its width and height
+    fn relative_offset(&self, preview_image_dimensions: [i32; 2]) -> [f32; 2] {
+        let offset = [self.offset_x_spin_row.value() as f32, self.offset_y_spin_row.value() as f32];
+
+        match self.offset_unit_toggle_group.active() {
+            1 => [offset[0] / 100.0, offset[1] / 100.0],
+            _ if preview_image_dimensions[0] > 0 && preview_image_dimensions[1] > 0 => [
+                offset[0] / preview_image_dimensions[0] as f32,
+                offset[1] / preview_image_dimensions[1] as f32,
+            ],
+            _ => [0.0, 0.0],
+        }
     }
+}
 
-    let range_correction: f64 = if preview_image_dimensions.borrow()[0] <= preview_image_dimensions.borrow()[1] { 1.0 / width_ratio } else { 1.0 / height_ratio };
+#[derive(Clone)]
+struct TilingRows {
+    horizontal_spacing_spin_row:    SpinRow,
+    vertical_spacing_spin_row:      SpinRow,
+    row_offset_spin_row:            SpinRow,
+}
 
+impl TilingRows {
+    // spacings are entered in pixels of the preview image, like the margin
+    fn tiling(&self, preview_image_width: f32) -> Tiling {
+        Tiling {
+            horizontal_spacing: self.horizontal_spacing_spin_row.value() as f32 / preview_image_width,
+            vertical_spacing: self.vertical_spacing_spin_row.value() as f32 / preview_image_width,
+            row_offset: (self.row_offset_spin_row.value() / 100.0) as f32,
+        }
+    }
+}
 
-    let width = (width_ratio * image_preview.width() as f64 * scale_slider_value * range_correction).ceil() as i32;
-    let height = (height_ratio * image_preview.height() as f64 * scale_slider_value * range_correction).ceil() as i32;
+#[derive(Clone)]
+struct OutputRows {
+    format_combo_row:           ComboRow,
+    webp_lossless_switch_row:   SwitchRow,
+    quality_spin_row:           SpinRow,
+    png_compression_combo_row:  ComboRow,
+    background_action_row:      ActionRow,
+    background_color_button:    ColorDialogButton,
+    name_entry_row:             EntryRow,
+    name_example_row:           ActionRow,
+}
 
-    let adjusted_margin = (margin_value as f32 * global_scale).ceil() as i32;
+impl OutputRows {
+    fn output_settings(&self) -> OutputSettings {
+        let background = self.background_color_button.rgba();
 
-    let x = (active_alignment_array[1] + active_alignment_array[3]) * (image_preview.width() - width - adjusted_margin)
-                + (active_alignment_array[0] + active_alignment_array[2]) * adjusted_margin;
-    let y = (active_alignment_array[2] + active_alignment_array[3]) * (image_preview.height() - height - adjusted_margin)
-                + (active_alignment_array[0] + active_alignment_array[1]) * adjusted_margin;
+        OutputSettings {
+            format: OutputFormat::from_index(self.format_combo_row.selected()),
+            quality: self.quality_spin_row.value() as u8,
+            png_compression: PngCompression::from_index(self.png_compression_combo_row.selected()),
+            webp_lossless: self.webp_lossless_switch_row.is_active(),
+            background: [background.red(), background.green(), background.blue()].map(|channel| (channel * 255.0).round() as u8),
+        }
+    }
 
-    return Rectangle::new(x, y, width, height);
+    // None keeps the original names
+    fn name_template(&self) -> Result<Option<NameTemplate>, WatermarkError> {
+        let template = self.name_entry_row.text();
+        if template.trim().is_empty() {
+            return Ok(None);
+        }
+        NameTemplate::parse(&template).map(Some)
+    }
+
+    // only shows the rows that apply to the chosen format, keeping the source format can need all of them
+    fn update_visibility(&self) {
+        let format = OutputFormat::from_index(self.format_combo_row.selected());
+        let lossy_webp = !self.webp_lossless_switch_row.is_active();
+
+        self.webp_lossless_switch_row.set_visible(matches!(format, OutputFormat::Keep | OutputFormat::WebP));
+        self.quality_spin_row.set_visible(match format {
+            OutputFormat::Keep | OutputFormat::Jpeg | OutputFormat::Avif => true,
+            OutputFormat::WebP => lossy_webp,
+            OutputFormat::Png => false,
+        });
+        self.png_compression_combo_row.set_visible(matches!(format, OutputFormat::Keep | OutputFormat::Png));
+        self.background_action_row.set_visible(matches!(format, OutputFormat::Keep | OutputFormat::Jpeg));
+    }
+}
+
+#[derive(Clone)]
+struct GeometryRows {
+    resize_combo_row:           ComboRow,
+    width_spin_row:             SpinRow,
+    height_spin_row:            SpinRow,
+    reframe_combo_row:          ComboRow,
+    aspect_ratio_combo_row:     ComboRow,
+    gravity_combo_row:          ComboRow,
+    pad_color_action_row:       ActionRow,
+    pad_color_button:           ColorDialogButton,
+}
+
+impl GeometryRows {
+    fn geometry(&self) -> Geometry {
+        let aspect_ratio = ASPECT_RATIOS.get(self.aspect_ratio_combo_row.selected() as usize).copied().unwrap_or([1, 1]);
+        let pad_color = self.pad_color_button.rgba();
+
+        let reframe = match self.reframe_combo_row.selected() {
+            1 => Some(Reframe::Crop {
+                aspect_ratio,
+                gravity: Anchor::from_index(self.gravity_combo_row.selected()),
+            }),
+            2 => Some(Reframe::Pad {
+                aspect_ratio,
+                color: [pad_color.red(), pad_color.green(), pad_color.blue(), pad_color.alpha()].map(|channel| (channel * 255.0).round() as u8),
+            }),
+            _ => None,
+        };
+
+        let [width, height] = [&self.width_spin_row, &self.height_spin_row].map(|spin_row| spin_row.value() as u32);
+        let resize = match self.resize_combo_row.selected() {
+            1 => Some(Resize::Fit { max_width: Some(width), max_height: Some(height) }),
+            2 => Some(Resize::Exact { width, height }),
+            _ => None,
+        };
+
+        Geometry { reframe, resize }
+    }
+
+    fn update_visibility(&self) {
+        let resized = self.resize_combo_row.selected() != 0;
+        let reframe = self.reframe_combo_row.selected();
+
+        self.width_spin_row.set_visible(resized);
+        self.height_spin_row.set_visible(resized);
+        self.aspect_ratio_combo_row.set_visible(reframe != 0);
+        self.gravity_combo_row.set_visible(reframe == 1);
+        self.pad_color_action_row.set_visible(reframe == 2);
+    }
+}
+
+// the single chosen path or how many folders and files were chosen, with the number of images
+fn input_summary(input_paths: &[PathBuf], image_count: usize) -> String {
+    let plural = |count: usize, noun: &str| format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" });
+
+    let chosen = match input_paths {
+        [input_path] => input_path.display().to_string(),
+        _ => {
+            let folder_count = input_paths.iter().filter(|input_path| input_path.is_dir()).count();
+            match (folder_count, input_paths.len() - folder_count) {
+                (0, file_count) => plural(file_count, "file"),
+                (folder_count, 0) => plural(folder_count, "folder"),
+                (folder_count, file_count) => format!("{} and {}", plural(folder_count, "folder"), plural(file_count, "file")),
+            }
+        }
+    };
+    format!("{} · {}", chosen, plural(image_count, "image"))
+}
+
+// `input_paths` followed by the `added_paths` that aren't in it yet
+fn with_added_paths(input_paths: &[PathBuf], added_paths: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut input_paths = input_paths.to_vec();
+    for added_path in added_paths {
+        if !input_paths.contains(&added_path) {
+            input_paths.push(added_path);
+        }
+    }
+    input_paths
+}
+
+// small upright copy of the image for the filmstrip, decoded at that size in GIO's thread pool
+async fn load_thumbnail(image_entry: &Path) -> Result<Texture, glib::Error> {
+    let image_stream = gio::File::for_path(image_entry).read_future(glib::Priority::LOW).await?;
+    let thumbnail_pixbuf = Pixbuf::from_stream_at_scale_future(&image_stream, THUMBNAIL_SIZE, THUMBNAIL_SIZE, true).await?;
+    let thumbnail_pixbuf = thumbnail_pixbuf.apply_embedded_orientation().unwrap_or(thumbnail_pixbuf);
+    Ok(Texture::for_pixbuf(&thumbnail_pixbuf))
+}
+
+fn texture_from_rgba(image: RgbaImage) -> MemoryTexture {
+    let (width, height) = image.dimensions();
+
+    MemoryTexture::new(
+        width as i32,
+        height as i32,
+        MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from_owned(image.into_raw()),
+        width as usize * 4,
+    )
+}
+
+fn clear_watermark_preview(
+    watermark_preview:              &Picture,
+    preview_watermark_dimensions:   &RefCell<[i32; 2]>,
+    preview_watermark_source:       &RefCell<Option<Arc<WatermarkSource>>>,
+) {
+    watermark_preview.set_paintable(None::<&Texture>);
+    *preview_watermark_dimensions.borrow_mut() = [0, 0];
+    *preview_watermark_source.borrow_mut() = None;
+}
+
+// renders the watermark turned by `rotation` degrees, the overlay sizes the picture to its bounding box
+fn set_rotated_watermark_preview(
+    watermark_preview:  &Picture,
+    watermark_source:   &WatermarkSource,
+    rotation:           f32,
+) {
+    let [width, height] = watermark_source.dimensions();
+    // text is rendered larger than its natural size so it stays sharp when the preview scales it up
+    let render_scale = match watermark_source {
+        WatermarkSource::Text(_) => 4.0,
+        WatermarkSource::Image(_) => (ROTATED_PREVIEW_SIZE / width.max(height) as f32).min(1.0),
+    };
+    let rendered_watermark = watermark_source.render([
+        (width as f32 * render_scale).round().max(1.0) as u32,
+        (height as f32 * render_scale).round().max(1.0) as u32,
+    ]);
+
+    watermark_preview.set_paintable(Some(&texture_from_rgba(rotate_watermark(&rendered_watermark, rotation))));
+}
+
+// shows a watermark image decoded by `load_image`, which already turned it upright
+fn set_image_watermark_preview(
+    watermark_image:                DynamicImage,
+    watermark_preview:              &Picture,
+    preview_watermark_dimensions:   &RefCell<[i32; 2]>,
+    preview_watermark_source:       &RefCell<Option<Arc<WatermarkSource>>>,
+    rotation:                       f32,
+) {
+    *preview_watermark_dimensions.borrow_mut() = [watermark_image.width() as i32, watermark_image.height() as i32];
+    let watermark_source = Arc::new(WatermarkSource::Image(watermark_image));
+
+    match &*watermark_source {
+        WatermarkSource::Image(watermark_image) if rotation == 0.0 => {
+            watermark_preview.set_paintable(Some(&texture_from_rgba(watermark_image.to_rgba8())));
+        }
+        _ => set_rotated_watermark_preview(watermark_preview, &watermark_source, rotation),
+    }
+    *preview_watermark_source.borrow_mut() = Some(watermark_source);
+}
+
+fn set_text_watermark_preview(
+    text_watermark_rows:            &TextWatermarkRows,
+    watermark_preview:              &Picture,
+    preview_watermark_dimensions:   &RefCell<[i32; 2]>,
+    preview_watermark_source:       &RefCell<Option<Arc<WatermarkSource>>>,
+    rotation:                       f32,
+) {
+    let text_renderer = match text_watermark_rows.text_watermark().renderer() {
+        Ok(text_renderer) => text_renderer,
+        Err(_) => {
+            clear_watermark_preview(watermark_preview, preview_watermark_dimensions, preview_watermark_source);
+            return;
+        }
+    };
+
+    let [text_width, text_height] = text_renderer.dimensions();
+    let watermark_source = WatermarkSource::Text(text_renderer);
+    set_rotated_watermark_preview(watermark_preview, &watermark_source, rotation);
+
+    *preview_watermark_dimensions.borrow_mut() = [text_width as i32, text_height as i32];
+    *preview_watermark_source.borrow_mut() = Some(Arc::new(watermark_source));
+}
+
+// renders the watermark with the export engine on a picture covering the whole preview image,
+// in normal blend mode only the watermark layer is drawn over the image preview
+fn set_rendered_watermark_preview(
+    rendered_watermark_preview:     &Picture,
+    preview_image:                  &RefCell<Option<RgbaImage>>,
+    preview_watermark_source:       &RefCell<Option<Arc<WatermarkSource>>>,
+    settings:                       &WatermarkSettings,
+) {
+    let preview_image = preview_image.borrow();
+    let preview_watermark_source = preview_watermark_source.borrow();
+
+    let (Some(preview_image), Some(watermark_source)) = (preview_image.as_ref(), preview_watermark_source.as_ref()) else {
+        rendered_watermark_preview.set_paintable(None::<&Texture>);
+        return;
+    };
+
+    let rendered_preview = match settings.blend_mode {
+        BlendMode::Normal => watermark_layer([preview_image.width(), preview_image.height()], watermark_source, settings),
+        _ => {
+            let mut composite = DynamicImage::ImageRgba8(preview_image.clone());
+            watermark_image(&mut composite, watermark_source, settings);
+            composite.into_rgba8()
+        }
+    };
+    rendered_watermark_preview.set_paintable(Some(&texture_from_rgba(rendered_preview)));
+}
+
+// a decoded preview image, pixbufs can't leave the thread they're made on so it holds their pixels
+struct LoadedPreviewImage {
+    pixel_bytes:            glib::Bytes,
+    has_alpha:              bool,
+    dimensions:             [i32; 2],
+    rowstride:              i32,
+    // smaller copy for the previews rendered by the export engine
+    preview_source_image:   RgbaImage,
+}
+
+impl LoadedPreviewImage {
+    fn pixbuf(&self) -> Pixbuf {
+        let [width, height] = self.dimensions;
+        Pixbuf::from_bytes(&self.pixel_bytes, Colorspace::Rgb, self.has_alpha, 8, width, height, self.rowstride)
+    }
+}
+
+// the preview image turned upright, called on a worker thread because large images take a while to decode
+fn load_preview_image(image_entry: &Path) -> Result<LoadedPreviewImage, glib::Error> {
+    let preview_pixbuf = Pixbuf::from_file(image_entry)?;
+    let preview_pixbuf = preview_pixbuf.apply_embedded_orientation().unwrap_or(preview_pixbuf);
+
+    let render_scale = (RENDERED_PREVIEW_SIZE / preview_pixbuf.width().max(preview_pixbuf.height()) as f32).min(1.0);
+    let render_pixbuf = preview_pixbuf.scale_simple(
+        (preview_pixbuf.width() as f32 * render_scale).round().max(1.0) as i32,
+        (preview_pixbuf.height() as f32 * render_scale).round().max(1.0) as i32,
+        InterpType::Bilinear,
+    );
+    let preview_source_image = rgba_from_pixbuf(render_pixbuf.as_ref().unwrap_or(&preview_pixbuf));
+
+    Ok(LoadedPreviewImage {
+        pixel_bytes: preview_pixbuf.read_pixel_bytes(),
+        has_alpha: preview_pixbuf.has_alpha(),
+        dimensions: [preview_pixbuf.width(), preview_pixbuf.height()],
+        rowstride: preview_pixbuf.rowstride(),
+        preview_source_image,
+    })
+}
+
+// pixels of the pixbuf, opaque when it has no alpha channel
+fn rgba_from_pixbuf(pixbuf: &Pixbuf) -> RgbaImage {
+    let channels = pixbuf.n_channels() as usize;
+    let rowstride = pixbuf.rowstride() as usize;
+    let has_alpha = pixbuf.has_alpha();
+    let pixel_bytes = pixbuf.read_pixel_bytes();
+
+    RgbaImage::from_fn(pixbuf.width() as u32, pixbuf.height() as u32, |x, y| {
+        let i = y as usize * rowstride + x as usize * channels;
+        let alpha = if has_alpha { pixel_bytes[i + 3] } else { 255 };
+        Rgba([pixel_bytes[i], pixel_bytes[i + 1], pixel_bytes[i + 2], alpha])
+    })
+}
+
+// spinner over the preview that stays visible while any image or watermark is still loading
+#[derive(Clone)]
+struct LoadingIndicator {
+    spinner:    Spinner,
+    pending:    Rc<Cell<u32>>,
+}
+
+impl LoadingIndicator {
+    fn start(&self) {
+        self.pending.set(self.pending.get() + 1);
+        self.spinner.set_visible(true);
+    }
+
+    fn finish(&self) {
+        self.pending.set(self.pending.get().saturating_sub(1));
+        self.spinner.set_visible(self.pending.get() > 0);
+    }
+
+    fn is_loading(&self) -> bool {
+        self.pending.get() > 0
+    }
+}
+
+// senders the watermarking thread reports to the main loop through
+#[derive(Clone)]
+struct WatermarkingChannels {
+    // false while watermarking, true to go back to the main page without results
+    state:      async_channel::Sender<bool>,
+    // fraction of the images done
+    progress:   async_channel::Sender<f64>,
+    toast:      async_channel::Sender<String>,
+    results:    async_channel::Sender<Vec<WatermarkResult>>,
+}
+
+// watermarks on a worker thread, `retries` limits the run to those source images of the job and
+// writes them to the output paths they got in the first run
+fn spawn_watermarking(
+    watermark_job:  Arc<WatermarkJob>,
+    retries:        Option<Vec<(PathBuf, PathBuf)>>,
+    batch_control:  Arc<BatchControl>,
+    channels:       WatermarkingChannels,
+) {
+    gio::spawn_blocking(move || {
+        channels.state
+            .send_blocking(false)
+            .expect("The confirm channel needs to be open.");
+
+        let progress = |progress: Progress| {
+            let fraction = match progress {
+                Progress::Started { .. } => 0.0,
+                Progress::Processed { completed, total, .. } => completed as f64 / total as f64,
+            };
+            channels.progress.send_blocking(fraction).expect("The progress channel needs to be open.");
+        };
+
+        let results = match &retries {
+            Some(retries) => retry_watermark(&watermark_job, retries, &batch_control, progress),
+            None => {
+                // nothing is written when images would overwrite each other's output
+                let target_paths = target_paths(&watermark_job);
+                let collisions = target_paths
+                    .iter()
+                    .filter(|target_path| matches!(target_path, Err(WatermarkError::NameCollision { .. })))
+                    .count();
+                if collisions > 0 {
+                    let _ = fs::remove_dir(&watermark_job.target_folder);
+                    channels.state
+                        .send_blocking(true)
+                        .expect("The confirm channel needs to be open.");
+                    channels.toast
+                        .send_blocking(format!("{} images would get the same file name, add {{counter}} or {{ext}} to the name.", collisions))
+                        .expect("The toast channel needs to be open.");
+                    return;
+                }
+
+                let results = apply_watermark(&watermark_job, target_paths, &batch_control, progress);
+                // a cancelled or entirely failed batch leaves nothing worth looking at
+                if !batch_control.is_cancelled() && results.iter().any(|result| result.result.is_ok()) {
+                    let _ = opener::open(&watermark_job.target_folder);
+                }
+                results
+            }
+        };
+
+        channels.results
+            .send_blocking(results)
+            .expect("The results channel needs to be open.");
+    });
+}
+
+// the page shown after a batch, listing the images that failed or were skipped
+#[derive(Clone)]
+struct ResultsPage {
+    title_label:            Label,
+    summary_label:          Label,
+    results_box:            Box,
+    retry_failed_button:    Button,
+    copy_report_button:     Button,
+}
+
+impl ResultsPage {
+    fn show(&self, target_folder: &Path, results: &[WatermarkResult]) {
+        let failed = results.iter().filter(|result| result.result.is_err() && !result.is_skipped()).collect::<Vec<_>>();
+        let skipped = results.iter().filter(|result| result.is_skipped()).collect::<Vec<_>>();
+        let succeeded = results.iter().filter(|result| result.result.is_ok()).collect::<Vec<_>>();
+        let cancelled = results.iter().any(|result| matches!(result.result, Err(WatermarkError::Cancelled)));
+
+        self.title_label.set_label(&match (cancelled, failed.len() + skipped.len()) {
+            (true, _) => format!("Cancelled after {} of {} images", succeeded.len(), results.len()),
+            (false, 0) => "All images watermarked".to_string(),
+            (false, _) => format!("{} of {} images watermarked", succeeded.len(), results.len()),
+        });
+        self.summary_label.set_label(&format!("{} succeeded, {} skipped, {} failed", succeeded.len(), skipped.len(), failed.len()));
+        // images that failed before getting an output path, e.g. to a bad name, would fail the same way again
+        self.retry_failed_button.set_visible(failed.iter().any(|result| result.target.is_some()));
+        self.copy_report_button.set_visible(!failed.is_empty() || !skipped.is_empty());
+
+        while let Some(child) = self.results_box.first_child() {
+            self.results_box.remove(&child);
+        }
+
+        // only the failed and skipped images get a row each, a batch can have thousands of succeeded ones
+        for (title, group_results) in [("Failed", failed), ("Skipped", skipped)] {
+            if group_results.is_empty() {
+                continue;
+            }
+
+            let results_group = PreferencesGroup::builder()
+                .title(format!("{} ({})", title, group_results.len()))
+                .build();
+            self.results_box.append(&results_group);
+
+            for result in group_results {
+                let mut subtitle = result.result.as_ref().err().map(ToString::to_string).unwrap_or_default();
+                if !result.is_skipped() && result.target.is_none() {
+                    subtitle.push_str(" · can't be retried");
+                }
+                let result_row = ActionRow::builder()
+                    .title(result.source.file_name().unwrap_or_default().to_string_lossy())
+                    .subtitle(subtitle)
+                    .tooltip_text(result.source.display().to_string())
+                    .use_markup(false)
+                    .build();
+                results_group.add(&result_row);
+            }
+        }
+
+        if !succeeded.is_empty() {
+            let results_group = PreferencesGroup::builder()
+                .title(format!("Succeeded ({})", succeeded.len()))
+                .build();
+            self.results_box.append(&results_group);
+
+            let open_folder_button = Button::builder()
+                .label("Open folder")
+                .valign(Align::Center)
+                .build();
+            open_folder_button.connect_clicked({
+                let target_folder = target_folder.to_path_buf();
+                move |_| {
+                    let _ = opener::open(&target_folder);
+                }
+            });
+
+            let folder_row = ActionRow::builder()
+                .title(format!("{} images written to", succeeded.len()))
+                .subtitle(target_folder.display().to_string())
+                .use_markup(false)
+                .activatable_widget(&open_folder_button)
+                .build();
+            folder_row.add_suffix(&open_folder_button);
+            results_group.add(&folder_row);
+        }
+    }
 }
 
+// plain text list of the images that failed or were skipped, for pasting into a bug report or mail
+fn error_report(watermark_job: &WatermarkJob, results: &[WatermarkResult]) -> String {
+    let mut report = format!("Cliquemark output folder: {}\n", watermark_job.target_folder.display());
+
+    for result in results {
+        if let Err(error) = &result.result {
+            let status = if result.is_skipped() { "skipped" } else { "failed" };
+            report.push_str(&format!("{} {}: {}\n", status, result.source.display(), error));
+        }
+    }
+    report
+}
 
 fn build_ui(app: &Application) {
     let window_default_size = (1500,900);
@@ -156,8 +835,32 @@ fn build_ui(app: &Application) {
     );
 
     main_window.set_icon_name(Some("my-app-icon"));
+
+    let style_provider = gtk::CssProvider::new();
+    style_provider.load_from_string(PREVIEW_STYLE);
+    gtk::style_context_add_provider_for_display(&WidgetExt::display(&*main_window), &style_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
     main_window.set_default_size(window_default_size.0, window_default_size.1);
 
+    let settings = load_settings();
+    if let Some(settings) = &settings {
+        main_window.set_default_size(settings.int("window-width"), settings.int("window-height"));
+        if settings.boolean("window-maximized") {
+            main_window.maximize();
+        }
+
+        main_window.connect_close_request({
+            let settings = settings.clone();
+            move |main_window| {
+                // the default size is the unmaximized size
+                let (window_width, window_height) = main_window.default_size();
+                let _ = settings.set_int("window-width", window_width);
+                let _ = settings.set_int("window-height", window_height);
+                let _ = settings.set_boolean("window-maximized", main_window.is_maximized());
+                glib::Propagation::Proceed
+            }
+        });
+    }
+
     let settings_header_container = Box::builder()
         .orientation(Orientation::Vertical)
         .vexpand(true)
@@ -176,7 +879,13 @@ fn build_ui(app: &Application) {
         .margin_start(50)
         .margin_end(50)
         .build();
-    settings_header_container.append(&settings_box_container);
+    // the groups are taller than the window, they scroll below the header
+    settings_header_container.append(&ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .child(&settings_box_container)
+        .build()
+    );
 
     // settings container 
     let settings_box = Box::builder()
@@ -197,6 +906,53 @@ fn build_ui(app: &Application) {
         .hexpand(true)
         .build();
     main_page_splitview.set_sidebar(Some(&settings_sidebar));
+
+    // named sets of watermark settings, the menu manages them and the dropdown applies one
+    let preset_menu = gio::Menu::new();
+    let preset_section = gio::Menu::new();
+    preset_section.append(Some("New From Current Settings…"), Some("preset.new"));
+    preset_section.append(Some("Save Current Settings"), Some("preset.save"));
+    preset_section.append(Some("Rename…"), Some("preset.rename"));
+    preset_section.append(Some("Duplicate"), Some("preset.duplicate"));
+    preset_section.append(Some("Delete…"), Some("preset.delete"));
+    preset_menu.append_section(None, &preset_section);
+    let preset_file_section = gio::Menu::new();
+    preset_file_section.append(Some("Import…"), Some("preset.import"));
+    preset_file_section.append(Some("Export…"), Some("preset.export"));
+    preset_menu.append_section(None, &preset_file_section);
+
+    let preset_menu_button = MenuButton::builder()
+        .icon_name("view-more-symbolic")
+        .tooltip_text("Manage presets")
+        .menu_model(&preset_menu)
+        .valign(Align::Center)
+        .build();
+    preset_menu_button.add_css_class("flat");
+
+    let presets_container = PreferencesGroup::builder()
+        .title("Presets")
+        .header_suffix(&preset_menu_button)
+        .margin_bottom(40)
+        .build();
+    settings_box.append(&presets_container);
+
+    let preset_names = StringList::new(&[]);
+    let preset_combo_row = ComboRow::builder()
+        .title("Preset")
+        .model(&preset_names)
+        .build();
+    presets_container.add(&preset_combo_row);
+
+    let preset_actions = gio::SimpleActionGroup::new();
+    main_window.insert_action_group("preset", Some(&preset_actions));
+
+    let preset_list = PresetList {
+        presets: Rc::new(RefCell::new(Vec::new())),
+        names: preset_names,
+        combo_row: preset_combo_row,
+        actions: preset_actions,
+        refreshing: Rc::new(Cell::new(false)),
+    };
     
     // Get rid of this grid, replace with nested gtkBox
     let selection_button_grid = Grid::builder()
@@ -209,14 +965,25 @@ fn build_ui(app: &Application) {
     selection_button_grid.set_column_spacing(12);    
     settings_box.append(&selection_button_grid);
 
-    // folder directory chooser
-    let choose_folder_button = Button::builder()
-        .label("Select Folder")
+    // the input is built from any mix of folders and image files
+    let input_button_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .homogeneous(true)
+        .spacing(6)
+        .hexpand(true)
+        .build();
+
+    let add_folders_button = Button::builder()
+        .label("Add Folders")
+        .hexpand(true)
+        .build();
+    input_button_box.append(&add_folders_button);
+
+    let add_images_button = Button::builder()
+        .label("Add Images")
         .hexpand(true)
-        // .vexpand(true)
-        .valign(Align::Fill)
-        .halign(Align::Fill)
         .build();
+    input_button_box.append(&add_images_button);
 
     let default_entry_text = EntryBuffer::new(Some("Nothing chosen"));
     let chosen_folder_text = Rc::new(Entry::builder()
@@ -230,8 +997,22 @@ fn build_ui(app: &Application) {
     // chosen_folder_text.set_alignment(0.5);
     EntryExt::set_alignment(&*chosen_folder_text, 0.5);
 
-    selection_button_grid.attach(&choose_folder_button, 0, 0, 1, 1);
-    selection_button_grid.attach(&*chosen_folder_text, 1,0,1,1);
+    let clear_input_button = Button::builder()
+        .icon_name("edit-clear-symbolic")
+        .tooltip_text("Clear the input")
+        .sensitive(false)
+        .build();
+    clear_input_button.add_css_class("flat");
+
+    let chosen_folder_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
+        .build();
+    chosen_folder_box.append(&*chosen_folder_text);
+    chosen_folder_box.append(&clear_input_button);
+
+    selection_button_grid.attach(&input_button_box, 0, 0, 1, 1);
+    selection_button_grid.attach(&chosen_folder_box, 1,0,1,1);
 
     // watermark chooser
     let choose_watermark_button = Button::builder()
@@ -257,32 +1038,221 @@ fn build_ui(app: &Application) {
     selection_button_grid.attach(&choose_watermark_button, 0, 1, 1, 1);
     selection_button_grid.attach(&*chosen_watermark_text, 1,1,1,1);
 
+    // image or text watermark
+    let image_source_toggle = Toggle::builder()
+        .label("Image watermark")
+        .build();
+    let text_source_toggle = Toggle::builder()
+        .label("Text watermark")
+        .build();
+    let watermark_source_toggle_group = Rc::new(ToggleGroup::builder()
+        .hexpand(true)
+        .build()
+    );
+    watermark_source_toggle_group.add(image_source_toggle);
+    watermark_source_toggle_group.add(text_source_toggle);
+    watermark_source_toggle_group.set_active(0);
+
+    selection_button_grid.attach(&*watermark_source_toggle_group, 0, 2, 2, 1);
+
+    // sub-folders are mirrored inside the output folder
+    let recursive_check_button = Rc::new(CheckButton::builder()
+        .label("Include sub-folders")
+        .build()
+    );
+    selection_button_grid.attach(&*recursive_check_button, 0, 3, 2, 1);
+
+
+    let text_watermark_container = PreferencesGroup::builder()
+        .title("Text Watermark")
+        .margin_bottom(40)
+        .visible(false)
+        .build();
+    settings_box.append(&text_watermark_container);
+
+    let text_entry_row = EntryRow::builder()
+        .title("Text")
+        .build();
+    text_watermark_container.add(&text_entry_row);
 
-    let top_left_toggle = Toggle::builder()
-        .label("Top left")
+    let font_action_row = ActionRow::builder()
+        .title("Font")
+        .subtitle("Sans")
+        .build();
+    let font_dialog_button = FontDialogButton::builder()
+        .dialog(&FontDialog::new())
+        .level(FontLevel::Family)
+        .font_desc(&FontDescription::from_string("Sans"))
+        .valign(Align::Center)
+        .build();
+    let font_file_button = Button::builder()
+        .icon_name("document-open-symbolic")
+        .tooltip_text("Open font file")
+        .valign(Align::Center)
+        .build();
+    font_file_button.add_css_class("flat");
+    font_action_row.add_suffix(&font_dialog_button);
+    font_action_row.add_suffix(&font_file_button);
+    text_watermark_container.add(&font_action_row);
+
+    let color_button = ColorDialogButton::builder()
+        .dialog(&ColorDialog::new())
+        .rgba(&RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .valign(Align::Center)
+        .build();
+    let color_action_row = ActionRow::builder()
+        .title("Colour")
         .build();
-    let top_right_toggle = Toggle::builder()
-        .label("Top right")
+    color_action_row.add_suffix(&color_button);
+    text_watermark_container.add(&color_action_row);
+
+    let weight_adjustment = Adjustment::new(400.0, 100.0, 900.0, 100.0, 100.0, 0.0);
+    let weight_spin_row = SpinRow::builder()
+        .title("Weight")
+        .adjustment(&weight_adjustment)
         .build();
-    let bottom_left_toggle = Toggle::builder()
-        .label("Bottom left")
+    text_watermark_container.add(&weight_spin_row);
+
+    let text_watermark_rows = Rc::new(TextWatermarkRows {
+        text_entry_row,
+        font_action_row,
+        font_source: Rc::new(RefCell::new(FontSource::default())),
+        color_button,
+        weight_spin_row,
+    });
+
+
+    let single_toggle = Toggle::builder()
+        .label("Single")
         .build();
-    let bottom_right_toggle = Toggle::builder()
-        .label("Bottom right")
+    let tiled_toggle = Toggle::builder()
+        .label("Tiled")
         .build();
-    // top_left_toggle.set_child(true);
-    let alignment_toggle_group = Rc::new(ToggleGroup::builder()
+    let placement_toggle_group = Rc::new(ToggleGroup::builder()
         .hexpand(true)
         .can_shrink(false)
         .build()
     );
-    alignment_toggle_group.add(top_left_toggle);
-    alignment_toggle_group.add(top_right_toggle);
-    alignment_toggle_group.add(bottom_left_toggle);
-    alignment_toggle_group.add(bottom_right_toggle);
-    alignment_toggle_group.set_active(3);
+    placement_toggle_group.add(single_toggle);
+    placement_toggle_group.add(tiled_toggle);
+    placement_toggle_group.set_active(0);
+
+    settings_box.append(&*placement_toggle_group);
+
+
+    let position_container = PreferencesGroup::builder()
+        .title("Position")
+        .build();
+    settings_box.append(&position_container);
 
-    settings_box.append(&*alignment_toggle_group);
+    // nine anchor points, laid out like the positions they stand for
+    let anchor_grid = Grid::builder()
+        .row_spacing(2)
+        .column_spacing(2)
+        .valign(Align::Center)
+        .margin_top(6)
+        .margin_bottom(6)
+        .build();
+    let anchor_labels = ["↖", "↑", "↗", "←", "•", "→", "↙", "↓", "↘"];
+
+    let mut anchor_buttons: Vec<ToggleButton> = Vec::new();
+    for (index, anchor) in Anchor::ALL.iter().enumerate() {
+        let anchor_button = ToggleButton::builder()
+            .label(anchor_labels[index])
+            .tooltip_text(anchor.name())
+            .active(*anchor == Anchor::default())
+            .build();
+        anchor_button.add_css_class("flat");
+        if let Some(first_anchor_button) = anchor_buttons.first() {
+            anchor_button.set_group(Some(first_anchor_button));
+        }
+
+        anchor_grid.attach(&anchor_button, (index % 3) as i32, (index / 3) as i32, 1, 1);
+        anchor_buttons.push(anchor_button);
+    }
+
+    let anchor_action_row = ActionRow::builder()
+        .title("Anchor")
+        .build();
+    anchor_action_row.add_suffix(&anchor_grid);
+    position_container.add(&anchor_action_row);
+
+    let offset_x_adjustment = Adjustment::new(0.0, -10000.0, 10000.0, 1.0, 10.0, 0.0);
+    let offset_x_spin_row = SpinRow::builder()
+        .title("X offset")
+        .digits(1)
+        .adjustment(&offset_x_adjustment)
+        .build();
+    position_container.add(&offset_x_spin_row);
+
+    let offset_y_adjustment = Adjustment::new(0.0, -10000.0, 10000.0, 1.0, 10.0, 0.0);
+    let offset_y_spin_row = SpinRow::builder()
+        .title("Y offset")
+        .digits(1)
+        .adjustment(&offset_y_adjustment)
+        .build();
+    position_container.add(&offset_y_spin_row);
+
+    let pixels_toggle = Toggle::builder()
+        .label("px")
+        .build();
+    let percent_toggle = Toggle::builder()
+        .label("%")
+        .build();
+    let offset_unit_toggle_group = ToggleGroup::builder()
+        .valign(Align::Center)
+        .build();
+    offset_unit_toggle_group.add(pixels_toggle);
+    offset_unit_toggle_group.add(percent_toggle);
+    offset_unit_toggle_group.set_active(0);
+
+    let offset_unit_action_row = ActionRow::builder()
+        .title("Offset unit")
+        .build();
+    offset_unit_action_row.add_suffix(&offset_unit_toggle_group);
+    position_container.add(&offset_unit_action_row);
+
+    let position_rows = Rc::new(PositionRows {
+        anchor_action_row,
+        anchor_buttons,
+        offset_x_spin_row,
+        offset_y_spin_row,
+        offset_unit_toggle_group,
+    });
+
+
+    let tiling_container = PreferencesGroup::builder()
+        .title("Tiling")
+        .visible(false)
+        .build();
+    settings_box.append(&tiling_container);
+
+    let horizontal_spacing_adjustment = Adjustment::new(0.0, 0.0, 1000.0, 1.0, 1.0, 0.0);
+    let horizontal_spacing_spin_row = SpinRow::builder()
+        .title("Horizontal spacing")
+        .adjustment(&horizontal_spacing_adjustment)
+        .build();
+    tiling_container.add(&horizontal_spacing_spin_row);
+
+    let vertical_spacing_adjustment = Adjustment::new(0.0, 0.0, 1000.0, 1.0, 1.0, 0.0);
+    let vertical_spacing_spin_row = SpinRow::builder()
+        .title("Vertical spacing")
+        .adjustment(&vertical_spacing_adjustment)
+        .build();
+    tiling_container.add(&vertical_spacing_spin_row);
+
+    let row_offset_adjustment = Adjustment::new(0.0, 0.0, 100.0, 5.0, 5.0, 0.0);
+    let row_offset_spin_row = SpinRow::builder()
+        .title("Row offset (%)")
+        .adjustment(&row_offset_adjustment)
+        .build();
+    tiling_container.add(&row_offset_spin_row);
+
+    let tiling_rows = Rc::new(TilingRows {
+        horizontal_spacing_spin_row,
+        vertical_spacing_spin_row,
+        row_offset_spin_row,
+    });
 
 
     let image_configs_container = PreferencesGroup::builder()
@@ -311,14 +1281,217 @@ fn build_ui(app: &Application) {
     image_configs_container.add(&settings_action_row);
 
 
-    let margin_adjustment = Adjustment::new(0.0, 0.0, 1000.0, 1.0, 1.0, 0.0);
-    let margin_spin_row = Rc::new(SpinRow::builder()
+    // opacity slider
+    let opacity_adjustment = Adjustment::new(100.0, 0.0, 100.0, 1.0, 1.0, 0.0); 
+    let opacity_slider = Rc::new(Scale::builder()
+        .digits(0)
+        .hexpand(true)
+        .draw_value(true)
+        .adjustment(&opacity_adjustment)
+        .width_request(f32::round(window_default_size.0 as f32 / 6.0) as i32)
+        .value_pos(PositionType::Right)
+        .build()
+    );
+
+    let opacity_action_row = ActionRow::builder()
+        .title("Opacity")
+        .build();
+    opacity_action_row.add_suffix(&*opacity_slider);
+    image_configs_container.add(&opacity_action_row);
+
+
+    // rotation slider, clockwise in degrees
+    let rotation_adjustment = Adjustment::new(0.0, -180.0, 180.0, 1.0, 15.0, 0.0); 
+    let rotation_slider = Rc::new(Scale::builder()
+        .digits(0)
+        .hexpand(true)
+        .draw_value(true)
+        .adjustment(&rotation_adjustment)
+        .width_request(f32::round(window_default_size.0 as f32 / 6.0) as i32)
+        .value_pos(PositionType::Right)
+        .build()
+    );
+    rotation_slider.add_mark(0.0, PositionType::Bottom, None);
+
+    let rotation_action_row = ActionRow::builder()
+        .title("Rotation")
+        .build();
+    rotation_action_row.add_suffix(&*rotation_slider);
+    image_configs_container.add(&rotation_action_row);
+
+
+    let blend_mode_names = BlendMode::ALL.map(|blend_mode| blend_mode.name());
+    let blend_mode_combo_row = ComboRow::builder()
+        .title("Blend mode")
+        .model(&StringList::new(&blend_mode_names))
+        .selected(BlendMode::default().index())
+        .build();
+    image_configs_container.add(&blend_mode_combo_row);
+
+
+    let margin_adjustment = Adjustment::new(0.0, 0.0, 1000.0, 1.0, 1.0, 0.0);
+    let margin_spin_row = Rc::new(SpinRow::builder()
         .title("Margin")
         .adjustment(&margin_adjustment)
         .build()
     );
     image_configs_container.add(&*margin_spin_row);
 
+
+    // resize and crop, applied before the watermark
+    let geometry_container = PreferencesGroup::builder()
+        .title("Resize and crop")
+        .build();
+    settings_box.append(&geometry_container);
+
+    let resize_combo_row = ComboRow::builder()
+        .title("Resize")
+        .model(&StringList::new(&["Original size", "Fit within", "Exact size"]))
+        .build();
+    geometry_container.add(&resize_combo_row);
+
+    let width_spin_row = SpinRow::builder()
+        .title("Width")
+        .adjustment(&Adjustment::new(2048.0, 1.0, 20000.0, 1.0, 100.0, 0.0))
+        .build();
+    geometry_container.add(&width_spin_row);
+
+    let height_spin_row = SpinRow::builder()
+        .title("Height")
+        .adjustment(&Adjustment::new(2048.0, 1.0, 20000.0, 1.0, 100.0, 0.0))
+        .build();
+    geometry_container.add(&height_spin_row);
+
+    let reframe_combo_row = ComboRow::builder()
+        .title("Aspect ratio")
+        .model(&StringList::new(&["Original", "Crop to", "Pad to"]))
+        .build();
+    geometry_container.add(&reframe_combo_row);
+
+    let aspect_ratio_names = ASPECT_RATIOS.map(|[width, height]| format!("{}:{}", width, height));
+    let aspect_ratio_combo_row = ComboRow::builder()
+        .title("Ratio")
+        .model(&StringList::new(&aspect_ratio_names.each_ref().map(String::as_str)))
+        .build();
+    geometry_container.add(&aspect_ratio_combo_row);
+
+    let gravity_names = Anchor::ALL.map(|anchor| anchor.name());
+    let gravity_combo_row = ComboRow::builder()
+        .title("Keep")
+        .subtitle("Part of the image that stays after cropping")
+        .model(&StringList::new(&gravity_names))
+        .selected(Anchor::Center.index())
+        .build();
+    geometry_container.add(&gravity_combo_row);
+
+    let pad_color_button = ColorDialogButton::builder()
+        .dialog(&ColorDialog::new())
+        .rgba(&RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .valign(Align::Center)
+        .build();
+    let pad_color_action_row = ActionRow::builder()
+        .title("Padding colour")
+        .build();
+    pad_color_action_row.add_suffix(&pad_color_button);
+    geometry_container.add(&pad_color_action_row);
+
+    let geometry_rows = Rc::new(GeometryRows {
+        resize_combo_row,
+        width_spin_row,
+        height_spin_row,
+        reframe_combo_row,
+        aspect_ratio_combo_row,
+        gravity_combo_row,
+        pad_color_action_row,
+        pad_color_button,
+    });
+    geometry_rows.update_visibility();
+
+
+    let output_container = PreferencesGroup::builder()
+        .title("Output")
+        .build();
+    settings_box.append(&output_container);
+
+    let format_names = OutputFormat::ALL.map(|format| format.name());
+    let format_combo_row = ComboRow::builder()
+        .title("Format")
+        .model(&StringList::new(&format_names))
+        .selected(OutputFormat::default().index())
+        .build();
+    output_container.add(&format_combo_row);
+
+    let webp_lossless_switch_row = SwitchRow::builder()
+        .title("Lossless WebP")
+        .subtitle("Off uses the quality below")
+        .active(OutputSettings::default().webp_lossless)
+        .build();
+    output_container.add(&webp_lossless_switch_row);
+
+    let quality_adjustment = Adjustment::new(OutputSettings::default().quality as f64, 1.0, 100.0, 1.0, 10.0, 0.0);
+    let quality_spin_row = SpinRow::builder()
+        .title("Quality")
+        .subtitle("JPEG, AVIF and lossy WebP")
+        .adjustment(&quality_adjustment)
+        .build();
+    output_container.add(&quality_spin_row);
+
+    let png_compression_names = PngCompression::ALL.map(|compression| compression.name());
+    let png_compression_combo_row = ComboRow::builder()
+        .title("PNG compression")
+        .model(&StringList::new(&png_compression_names))
+        .selected(PngCompression::default().index())
+        .build();
+    output_container.add(&png_compression_combo_row);
+
+    let background_color_button = ColorDialogButton::builder()
+        .dialog(&ColorDialog::builder().with_alpha(false).build())
+        .rgba(&RGBA::new(1.0, 1.0, 1.0, 1.0))
+        .valign(Align::Center)
+        .build();
+    let background_action_row = ActionRow::builder()
+        .title("Background")
+        .subtitle("Fills transparent areas when saving as JPEG")
+        .build();
+    background_action_row.add_suffix(&background_color_button);
+    output_container.add(&background_action_row);
+
+    let name_tokens = NAME_TOKENS.map(|(token, description)| format!("{}  {}", token, description)).join("\n");
+    let name_entry_row = EntryRow::builder()
+        .title("File names, e.g. client_{date}_{counter:04}")
+        .tooltip_text(format!("Empty keeps the original names, a / creates sub-folders and the extension is added automatically.\n\n{}", name_tokens))
+        .build();
+    output_container.add(&name_entry_row);
+
+    let name_example_row = ActionRow::builder()
+        .title("Example")
+        .subtitle("Original names")
+        .use_markup(false)
+        .build();
+    name_example_row.add_css_class("property");
+    output_container.add(&name_example_row);
+
+    let output_rows = Rc::new(OutputRows {
+        format_combo_row,
+        webp_lossless_switch_row,
+        quality_spin_row,
+        png_compression_combo_row,
+        background_action_row,
+        background_color_button,
+        name_entry_row,
+        name_example_row,
+    });
+
+    output_rows.format_combo_row.connect_selected_notify({
+        let output_rows = Rc::clone(&output_rows);
+        move |_| output_rows.update_visibility()
+    });
+
+    output_rows.webp_lossless_switch_row.connect_active_notify({
+        let output_rows = Rc::clone(&output_rows);
+        move |_| output_rows.update_visibility()
+    });
+
     
     // confirm button
     let confirm_button = Button::builder()
@@ -335,6 +1508,26 @@ fn build_ui(app: &Application) {
         .build();
     preview_header.add_css_class("flat");
 
+    // shows the selected image rendered by the export itself instead of the live overlays
+    let exact_preview_button = ToggleButton::builder()
+        .label("Exact Preview")
+        .tooltip_text("Show the image exactly as it will be saved")
+        .build();
+    preview_header.pack_end(&exact_preview_button);
+
+    // the original left of a draggable divider and the watermarked image right of it
+    let compare_button = ToggleButton::builder()
+        .icon_name("object-flip-horizontal-symbolic")
+        .tooltip_text("Compare with the original")
+        .build();
+    preview_header.pack_end(&compare_button);
+
+    let show_original_button = Button::builder()
+        .icon_name("image-x-generic-symbolic")
+        .tooltip_text("Hold to show the original (\\)")
+        .build();
+    preview_header.pack_end(&show_original_button);
+
     
     let header_container = Box::builder()
         .orientation(Orientation::Vertical)
@@ -364,7 +1557,6 @@ fn build_ui(app: &Application) {
         .hexpand(true)
         .vexpand(true)
         .build();
-    header_container.append(&preview_side_box);  
       
     let preview_side_sub_box = Box::builder()
         .orientation(Orientation::Vertical)
@@ -388,319 +1580,1738 @@ fn build_ui(app: &Application) {
     );
     preview_widget.set_child(Some(&*image_preview));
 
+    let preview_image_dimensions: Rc<RefCell<[i32; 2]>> = Rc::new(RefCell::new([0, 0]));
+    let preview_watermark_dimensions: Rc<RefCell<[i32; 2]>> = Rc::new(RefCell::new([0, 0]));
+
+    let viewport = PreviewViewport::new(
+        &preview_side_box,
+        &preview_side_sub_box,
+        &preview_widget,
+        &image_preview,
+        &preview_image_dimensions,
+    );
+    header_container.append(&viewport.area);
+    preview_header.pack_start(&viewport.zoom_toggle_group);
+    preview_header.pack_start(&viewport.zoom_label);
+    preview_header.pack_start(&viewport.jump_to_watermark_button);
+
+    // the watermark's box in preview pixels, as the overlay places it
+    let watermark_rectangle: Rc<dyn Fn() -> Rectangle> = Rc::new({
+        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let image_preview = Rc::clone(&image_preview);
+        let scale_slider = Rc::clone(&scale_slider);
+        let margin_spin_row = Rc::clone(&margin_spin_row);
+        let position_rows = Rc::clone(&position_rows);
+        let rotation_slider = Rc::clone(&rotation_slider);
+
+        move || calculate_watermark_position(
+            &preview_image_dimensions,
+            &preview_watermark_dimensions,
+            &image_preview,
+            &scale_slider.value(),
+            margin_spin_row.value() as i32,
+            position_rows.anchor(),
+            position_rows.relative_offset(*preview_image_dimensions.borrow()),
+            rotation_slider.value() as f32,
+        )
+    });
+
     let watermark_preview = Rc::new(Picture::builder()
         .build()
     );
     
     preview_widget.add_overlay(&*watermark_preview);
+
+    let rendered_watermark_preview = Rc::new(Picture::builder()
+        .visible(false)
+        .build()
+    );
+    preview_widget.add_overlay(&*rendered_watermark_preview);
+
+    // dragging the watermark moves it, the handles at its corners resize it around the opposite corner
+    let watermark_dragging = WatermarkDragging::new(
+        &preview_widget,
+        &image_preview,
+        &preview_image_dimensions,
+        &position_rows,
+        &scale_slider,
+        &margin_spin_row,
+        Rc::clone(&watermark_rectangle),
+    );
+
+    // the original image left of the comparison divider
+    let original_preview = Picture::builder()
+        .build();
+    let comparison = PreviewComparison::new(&compare_button, &original_preview, &preview_widget, &image_preview);
+
+    let preview_loading_spinner = Spinner::builder()
+        .width_request(48)
+        .height_request(48)
+        .halign(Align::Center)
+        .valign(Align::Center)
+        .visible(false)
+        .build();
+    preview_widget.add_overlay(&preview_loading_spinner);
+    let loading_indicator = LoadingIndicator {
+        spinner: preview_loading_spinner.clone(),
+        pending: Rc::new(Cell::new(0)),
+    };
+
+    // browses the input images, the selected thumbnail is the one in the preview
+    let preview_browser_box = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin_start(50)
+        .margin_end(50)
+        .margin_bottom(24)
+        .visible(false)
+        .build();
+    header_container.append(&preview_browser_box);
+
+    let preview_navigation_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .halign(Align::Center)
+        .spacing(6)
+        .build();
+    preview_browser_box.append(&preview_navigation_box);
+
+    let previous_image_button = Button::builder()
+        .icon_name("go-previous-symbolic")
+        .tooltip_text("Previous image")
+        .build();
+    previous_image_button.add_css_class("flat");
+    preview_navigation_box.append(&previous_image_button);
+
+    let preview_position_label = Label::builder()
+        .width_chars(12)
+        .build();
+    preview_position_label.add_css_class("dim-label");
+    preview_position_label.add_css_class("numeric");
+    preview_navigation_box.append(&preview_position_label);
+
+    let next_image_button = Button::builder()
+        .icon_name("go-next-symbolic")
+        .tooltip_text("Next image")
+        .build();
+    next_image_button.add_css_class("flat");
+    preview_navigation_box.append(&next_image_button);
+
+    let shuffle_image_button = Button::builder()
+        .icon_name("media-playlist-shuffle-symbolic")
+        .tooltip_text("Random image")
+        .build();
+    shuffle_image_button.add_css_class("flat");
+    preview_navigation_box.append(&shuffle_image_button);
+
+    // one item per input image, the thumbnails are looked up by position in the input
+    let filmstrip_names = StringList::new(&[]);
+    let filmstrip_selection = SingleSelection::builder()
+        .model(&filmstrip_names)
+        .autoselect(false)
+        .can_unselect(true)
+        .build();
+    let thumbnail_factory = SignalListItemFactory::new();
+    let filmstrip_list_view = ListView::builder()
+        .model(&filmstrip_selection)
+        .factory(&thumbnail_factory)
+        .orientation(Orientation::Horizontal)
+        .build();
+    filmstrip_list_view.add_css_class("navigation-sidebar");
+    preview_browser_box.append(&ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Automatic)
+        .vscrollbar_policy(PolicyType::Never)
+        .child(&filmstrip_list_view)
+        .build()
+    );
     
-    alignment_toggle_group.connect_active_notify({
+    placement_toggle_group.connect_active_notify({
         let preview_widget = Rc::clone(&preview_widget);
         move |_| {
             let _ = &preview_widget.queue_allocate();
         }
     });
 
-    let preview_image_dimensions: Rc<RefCell<[i32; 2]>> = Rc::new(RefCell::new([0, 0]));
-    let preview_watermark_dimensions: Rc<RefCell<[i32; 2]>> = Rc::new(RefCell::new([0, 0]));
+    // the chosen preview image as loaded, and its downscaled copy before resizing and cropping
+    let preview_pixbuf: Rc<RefCell<Option<Pixbuf>>> = Rc::new(RefCell::new(None));
+    let preview_source_image: Rc<RefCell<Option<RgbaImage>>> = Rc::new(RefCell::new(None));
+    // the chosen folders and image files, and the images they hold in batch order
+    let chosen_inputs: Rc<RefCell<Vec<PathBuf>>> = Rc::new(RefCell::new(Vec::new()));
+    let input_entries: Rc<RefCell<Vec<PathBuf>>> = Rc::new(RefCell::new(Vec::new()));
+    // input images that failed to decode, skipped when the preview falls back to another image
+    let unreadable_entries: Rc<RefCell<HashSet<PathBuf>>> = Rc::new(RefCell::new(HashSet::new()));
+    // thumbnails already decoded for the filmstrip
+    let thumbnail_cache: Rc<RefCell<HashMap<PathBuf, Texture>>> = Rc::new(RefCell::new(HashMap::new()));
+    // path of the preview image and its position in the batch, for the file name example
+    let preview_entry: Rc<RefCell<Option<(PathBuf, usize)>>> = Rc::new(RefCell::new(None));
+    let preview_image: Rc<RefCell<Option<RgbaImage>>> = Rc::new(RefCell::new(None));
+    let preview_watermark_source: Rc<RefCell<Option<Arc<WatermarkSource>>>> = Rc::new(RefCell::new(None));
 
     preview_widget.connect_get_child_position(
     {
-        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
-        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
         let image_preview = Rc::clone(&image_preview);
-        let scale_slider = Rc::clone(&scale_slider);
-        let margin_input = Rc::clone(&margin_spin_row);
+        let rendered_watermark_preview = Rc::clone(&rendered_watermark_preview);
+        let watermark_rectangle = Rc::clone(&watermark_rectangle);
 
+        let preview_loading_spinner = preview_loading_spinner.clone();
+        let comparison = comparison.clone();
+        let watermark_dragging = watermark_dragging.clone();
 
-        let alignment_toggle_group = Rc::clone(&alignment_toggle_group);
+        move |_, overlay_child| {
+            // centred by its alignment
+            if overlay_child == preview_loading_spinner.upcast_ref::<gtk::Widget>() {
+                return None;
+            }
+            if let Some(rectangle) = comparison.child_rectangle(overlay_child) {
+                return Some(rectangle);
+            }
 
-        move |_, _watermark_preview| {
-            let alignment_config_array: [i32; 4] = match alignment_toggle_group.active() {
-                0 => [1, 0, 0, 0],
-                1 => [0, 1, 0, 0],
-                2 => [0, 0, 1, 0],
-                3 => [0, 0, 0, 1],
-                _ => [0, 0, 0, 1],
-            };
+            if overlay_child == rendered_watermark_preview.upcast_ref::<gtk::Widget>() {
+                return Some(Rectangle::new(0, 0, image_preview.width(), image_preview.height()));
+            }
+            if let Some(rectangle) = watermark_dragging.child_rectangle(overlay_child) {
+                return Some(rectangle);
+            }
+            return Some(watermark_rectangle());
+        }
+    });    
 
-            let watermark_rectangle = calculate_watermark_position(
+    // the settings the export uses, relative to the preview image so they fit every image of the batch
+    let export_settings = Rc::new({
+        let image_preview = Rc::clone(&image_preview);
+        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let placement_toggle_group = Rc::clone(&placement_toggle_group);
+        let position_rows = Rc::clone(&position_rows);
+        let tiling_rows = Rc::clone(&tiling_rows);
+        let scale_slider = Rc::clone(&scale_slider);
+        let opacity_slider = Rc::clone(&opacity_slider);
+        let rotation_slider = Rc::clone(&rotation_slider);
+        let blend_mode_combo_row = blend_mode_combo_row.clone();
+        let margin_spin_row = Rc::clone(&margin_spin_row);
+
+        move || {
+            let relative_margin_width = margin_spin_row.value() as f32 / preview_image_dimensions.borrow()[0] as f32;
+            // the watermark widget spans the rotated bounding box, the surface area is the unrotated watermark's
+            let unrotated_watermark_rectangle = calculate_watermark_position(
                 &preview_image_dimensions,
                 &preview_watermark_dimensions,
                 &image_preview,
                 &scale_slider.value(),
-                margin_input.value() as i32,
-                alignment_config_array,
+                margin_spin_row.value() as i32,
+                position_rows.anchor(),
+                [0.0, 0.0],
+                0.0,
             );
-            return Some(watermark_rectangle);
+            let relative_surface_area = unrotated_watermark_rectangle.width() as f32 * unrotated_watermark_rectangle.height() as f32 / (image_preview.width() as f32 * image_preview.height() as f32);
+
+            WatermarkSettings {
+                relative_surface_area,
+                relative_margin_width,
+                anchor: position_rows.anchor(),
+                relative_offset: position_rows.relative_offset(*preview_image_dimensions.borrow()),
+                rotation: rotation_slider.value() as f32,
+                tiling: (placement_toggle_group.active() == 1).then(|| tiling_rows.tiling(preview_image_dimensions.borrow()[0] as f32)),
+                opacity: (opacity_slider.value() / 100.0) as f32,
+                blend_mode: BlendMode::from_index(blend_mode_combo_row.selected()),
+            }
         }
-    });    
+    });
 
-    scale_slider.connect_value_changed({
-        let preview_widget = Rc::clone(&preview_widget);        
-        move |_| {
-            let _ = &preview_widget.queue_allocate();
+    // the exact preview renders the selected image with the export pipeline on a worker thread,
+    // one render runs at a time and changes made meanwhile start a single new one when it's done
+    let (exact_preview_sender, exact_preview_receiver) = async_channel::bounded(1);
+    let exact_preview_running = Rc::new(Cell::new(false));
+    let exact_preview_outdated = Rc::new(Cell::new(false));
+
+    let start_exact_preview = Rc::new({
+        let export_settings = Rc::clone(&export_settings);
+        let geometry_rows = Rc::clone(&geometry_rows);
+        let output_rows = Rc::clone(&output_rows);
+        let image_preview = Rc::clone(&image_preview);
+        let preview_entry = Rc::clone(&preview_entry);
+        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let exact_preview_running = Rc::clone(&exact_preview_running);
+
+        move || {
+            let Some((image_entry, _)) = preview_entry.borrow().clone() else {
+                return;
+            };
+            let Some(watermark_source) = preview_watermark_source.borrow().clone() else {
+                return;
+            };
+            let [image_width, image_height] = *preview_image_dimensions.borrow();
+            let [watermark_width, watermark_height] = *preview_watermark_dimensions.borrow();
+            if image_width <= 0 || image_height <= 0 || watermark_width <= 0 || watermark_height <= 0 {
+                return;
+            }
+
+            let settings = export_settings();
+            let geometry = geometry_rows.geometry();
+            let output = output_rows.output_settings();
+            // rendered at the size the picture is shown at on screen, not larger
+            let scale_factor = image_preview.scale_factor();
+            let max_dimensions = [image_preview.width(), image_preview.height()].map(|length| (length * scale_factor).max(1) as u32);
+
+            exact_preview_running.set(true);
+            let exact_preview_sender = exact_preview_sender.clone();
+            gio::spawn_blocking(move || {
+                let rendered_preview = render_preview(&image_entry, &geometry, &watermark_source, &settings, &output, max_dimensions);
+                exact_preview_sender
+                    .send_blocking(rendered_preview)
+                    .expect("The exact preview channel needs to be open.");
+            });
         }
     });
 
-    margin_spin_row.connect_value_notify({
-        let preview_widget = Rc::clone(&preview_widget);        
-        move |_| {
-            let _ = &preview_widget.queue_allocate();
+    let request_exact_preview = Rc::new({
+        let exact_preview_button = exact_preview_button.clone();
+        let exact_preview_running = Rc::clone(&exact_preview_running);
+        let exact_preview_outdated = Rc::clone(&exact_preview_outdated);
+        let start_exact_preview = Rc::clone(&start_exact_preview);
+
+        move || {
+            if !exact_preview_button.is_active() {
+                return;
+            }
+            if exact_preview_running.get() {
+                exact_preview_outdated.set(true);
+                return;
+            }
+            start_exact_preview();
         }
     });
 
+    // Queue the async block to show the exact previews, a render that's already outdated is replaced right away
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        exact_preview_button,
+        #[strong]
+        image_preview,
+        #[strong]
+        toast_overlay,
+        async move {
+            while let Ok(rendered_preview) = exact_preview_receiver.recv().await {
+                exact_preview_running.set(false);
+                if exact_preview_outdated.replace(false) && exact_preview_button.is_active() {
+                    start_exact_preview();
+                    continue;
+                }
+                if !exact_preview_button.is_active() {
+                    continue;
+                }
 
-    let loader_header_container = Box::builder()
-        .orientation(Orientation::Vertical)
-        .build();
+                match rendered_preview {
+                    Ok(rendered_preview) => image_preview.set_paintable(Some(&texture_from_rgba(rendered_preview))),
+                    Err(error) => {
+                        let toast_message = Toast::builder()
+                            .title(format!("The exact preview failed: {}.", error))
+                            .build();
 
-    let loader_navigation_page = NavigationPage::builder()
-    .child(&loader_header_container)
-    .title("Cliquemark")    
-    .build();
+                        toast_overlay.add_toast(toast_message);
+                    }
+                }
+            }
+        }
+    ));
 
-    let loader_header = HeaderBar::builder()
-        .build();
-    loader_header.add_css_class("flat");
-    loader_header_container.append(&loader_header);
+    // the exact preview is rendered at the zoomed size
+    viewport.connect_zoom_changed({
+        let request_exact_preview = Rc::clone(&request_exact_preview);
+        move || request_exact_preview()
+    });
+    viewport.connect(Rc::clone(&watermark_rectangle));
+
+    // only renders for tiles and blend modes GTK can't show, a single normal watermark is positioned by the overlay
+    let update_rendered_watermark_preview = Rc::new({
+        let placement_toggle_group = Rc::clone(&placement_toggle_group);
+        let position_rows = Rc::clone(&position_rows);
+        let tiling_container = tiling_container.clone();
+        let tiling_rows = Rc::clone(&tiling_rows);
+        let watermark_preview = Rc::clone(&watermark_preview);
+        let rendered_watermark_preview = Rc::clone(&rendered_watermark_preview);
+        let preview_image = Rc::clone(&preview_image);
+        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let scale_slider = Rc::clone(&scale_slider);
+        let opacity_slider = Rc::clone(&opacity_slider);
+        let rotation_slider = Rc::clone(&rotation_slider);
+        let blend_mode_combo_row = blend_mode_combo_row.clone();
+        let margin_spin_row = Rc::clone(&margin_spin_row);
+        let exact_preview_button = exact_preview_button.clone();
+        let request_exact_preview = Rc::clone(&request_exact_preview);
+        let watermark_dragging = watermark_dragging.clone();
+
+        move || {
+            let tiled = placement_toggle_group.active() == 1;
+            let blend_mode = BlendMode::from_index(blend_mode_combo_row.selected());
+            let rendered = tiled || blend_mode != BlendMode::Normal;
+            // the exact preview already contains the watermark
+            let exact = exact_preview_button.is_active();
+            let has_watermark = preview_watermark_dimensions.borrow()[0] > 0;
+
+            position_rows.anchor_action_row.set_visible(!tiled);
+            tiling_container.set_visible(tiled);
+            watermark_preview.set_visible(!rendered && !exact);
+            rendered_watermark_preview.set_visible(rendered && !exact);
+            // only a single watermark shown by the overlay can be moved and resized directly
+            watermark_dragging.set_handles_visible(!rendered && !exact && has_watermark);
+
+            if exact {
+                request_exact_preview();
+                return;
+            }
+            if !rendered {
+                return;
+            }
 
-    let loader_page_container = Box::builder()
-    .orientation(Orientation::Vertical)
-    .valign(Align::Center)
-    .halign(Align::Center)
-    .vexpand(true)
-    .build();
-    loader_header_container.append(&loader_page_container);
-    
-    main_stack.add_named(&loader_navigation_page, Some("loader_page"));
+            let [image_width, image_height] = *preview_image_dimensions.borrow();
+            let [watermark_width, watermark_height] = *preview_watermark_dimensions.borrow();
+            if image_width <= 0 || image_height <= 0 || watermark_width <= 0 || watermark_height <= 0 {
+                rendered_watermark_preview.set_paintable(None::<&Texture>);
+                return;
+            }
 
-    let watermark_loading_spinner = Spinner::builder()
-        .height_request(50)
-        .build();
-    loader_page_container.append(&watermark_loading_spinner);
+            let settings = WatermarkSettings {
+                relative_offset: position_rows.relative_offset([image_width, image_height]),
+                rotation: rotation_slider.value() as f32,
+                tiling: tiled.then(|| tiling_rows.tiling(image_width as f32)),
+                opacity: (opacity_slider.value() / 100.0) as f32,
+                blend_mode,
+                ..WatermarkSettings::from_scale(
+                    scale_slider.value(),
+                    margin_spin_row.value(),
+                    [image_width as u32, image_height as u32],
+                    [watermark_width as u32, watermark_height as u32],
+                    position_rows.anchor(),
+                )
+            };
+            set_rendered_watermark_preview(&rendered_watermark_preview, &preview_image, &preview_watermark_source, &settings);
+        }
+    });
 
-    let watermark_progress_bar = Rc::new(ProgressBar::builder()
-        .width_request(300)
-        .margin_top(30)
-        .show_text(true)
-        .build()
-    );
-    loader_page_container.append(&*watermark_progress_bar);
+    placement_toggle_group.connect_active_notify({
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |_| update_rendered_watermark_preview()
+    });
 
-    // let cancel_button = Button::builder()
-    //     .halign(Align::Center)
-    //     .label("Cancel")
-    //     .margin_top(70)
-    //     .build();
-    // cancel_button.add_css_class("destructive-action");
-    // cancel_button.add_css_class("pill");
-    // loader_page_container.append(&cancel_button);
+    watermark_dragging.connect(&watermark_preview);
+
+    // output name of the preview image, or why the template can't be used
+    let update_name_example = Rc::new({
+        let geometry_rows = Rc::clone(&geometry_rows);
+        let output_rows = Rc::clone(&output_rows);
+        let preview_entry = Rc::clone(&preview_entry);
+
+        move || {
+            let example = match (output_rows.name_template(), preview_entry.borrow().as_ref()) {
+                (Ok(None), _) => Ok("Original names".to_string()),
+                (Ok(Some(_)), None) => Ok("Choose a folder to see an example".to_string()),
+                (Ok(Some(name_template)), Some((image_entry, index))) => name_template
+                    .target_name(
+                        image_entry,
+                        index + 1,
+                        &geometry_rows.geometry(),
+                        OutputFormat::from_index(output_rows.format_combo_row.selected()),
+                    )
+                    .map(|target_name| target_name.display().to_string()),
+                (Err(error), _) => Err(error),
+            };
 
-    
+            match example {
+                Ok(example) => {
+                    output_rows.name_example_row.remove_css_class("error");
+                    output_rows.name_example_row.set_subtitle(&example);
+                }
+                Err(error) => {
+                    output_rows.name_example_row.add_css_class("error");
+                    output_rows.name_example_row.set_subtitle(&error.to_string());
+                }
+            }
+        }
+    });
 
-    choose_folder_button.connect_clicked({
-        let main_window = Rc::clone(&main_window);
-        let watermark_progress_bar = Rc::clone(&watermark_progress_bar);
-        let chosen_folder_text= Rc::clone(&chosen_folder_text);
-        let image_preview = Rc::clone(&image_preview);
-        let toast_overlay = Rc::clone(&toast_overlay);
+    output_rows.name_entry_row.connect_changed({
+        let update_name_example = Rc::clone(&update_name_example);
+        move |_| update_name_example()
+    });
+
+    output_rows.format_combo_row.connect_selected_notify({
+        let update_name_example = Rc::clone(&update_name_example);
+        move |_| update_name_example()
+    });
 
+    // resizes and crops the preview like the export, so the watermark is placed on the final image
+    let update_preview_geometry = Rc::new({
+        let update_name_example = Rc::clone(&update_name_example);
+        let geometry_rows = Rc::clone(&geometry_rows);
+        let preview_widget = Rc::clone(&preview_widget);
+        let image_preview = Rc::clone(&image_preview);
+        let preview_pixbuf = Rc::clone(&preview_pixbuf);
+        let preview_source_image = Rc::clone(&preview_source_image);
+        let preview_image = Rc::clone(&preview_image);
         let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        let exact_preview_button = exact_preview_button.clone();
+        let viewport = viewport.clone();
+        let original_preview = original_preview.clone();
+
+        move || {
+            // the example's {width} and {height} follow the geometry
+            update_name_example();
+
+            let preview_pixbuf = preview_pixbuf.borrow();
+            let preview_source_image = preview_source_image.borrow();
+            let (Some(preview_pixbuf), Some(preview_source_image)) = (preview_pixbuf.as_ref(), preview_source_image.as_ref()) else {
+                // nothing left to zoom into
+                viewport.set_zoom(None, None);
+                return;
+            };
 
-        move |_| {
-            let folder_dialog = FileDialog::builder()
-            .title("Select Folder")
-            .build();
+            let geometry = geometry_rows.geometry();
+            let source_dimensions = [preview_pixbuf.width() as u32, preview_pixbuf.height() as u32];
+            let [image_width, image_height] = geometry.dimensions(source_dimensions);
+            *preview_image_dimensions.borrow_mut() = [image_width as i32, image_height as i32];
 
-            let chosen_folder_text = Rc::clone(&chosen_folder_text);            
-            let image_preview = Rc::clone(&image_preview);
-            let watermark_progress_bar = Rc::clone(&watermark_progress_bar);
-            let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
-            let toast_overlay = Rc::clone(&toast_overlay);
-            
-            folder_dialog.select_folder(Some(&*main_window),None::<&gtk::gio::Cancellable>, 
-            move |result| {
-                let folder_path: PathBuf;
-                if let Ok(path) = result {
-                    folder_path = path.path().unwrap();
-                } else {
-                    return;
-                }
+            // without a geometry the full resolution image stays sharp in the preview,
+            // the exact preview keeps showing its last render until the new one is done
+            let original_texture = if geometry == Geometry::default() {
+                *preview_image.borrow_mut() = Some(preview_source_image.clone());
+                Texture::for_pixbuf(preview_pixbuf)
+            } else {
+                let framed_image = geometry
+                    .apply_preview(DynamicImage::ImageRgba8(preview_source_image.clone()), source_dimensions)
+                    .into_rgba8();
+                *preview_image.borrow_mut() = Some(framed_image.clone());
+                texture_from_rgba(framed_image).upcast()
+            };
+            if !exact_preview_button.is_active() {
+                image_preview.set_paintable(Some(&original_texture));
+            }
+            original_preview.set_paintable(Some(&original_texture));
 
-                let entries = std::fs::read_dir(&folder_path).unwrap()
-                .filter_map(|entry| {
-                    let entry = entry.ok()?;
-                    let path = entry.path();
-                    if is_image_file(&path) {
-                        Some(path)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-                
-                if entries.is_empty() {
-                    let no_images_found_toast = Toast::builder()
-                        .title("No images found in chosen folder.")
-                        .build();
-                    
-                    toast_overlay.add_toast(no_images_found_toast);
-                    return;
-                }
-                
-                chosen_folder_text.set_text(folder_path.to_str().unwrap());
-                chosen_folder_text.set_position(-1);
-
-                watermark_progress_bar.set_pulse_step(1.0 / entries.len() as f64);
-
-                let mut rng = rand::rng();
-                let random_preview_entry;
-                if let Some(random_image) = entries.choose(&mut rng) {
-                    random_preview_entry = random_image;
-                } else {
-                    todo!();
-                };
-                
-                let mut preview_image_pixbuf = Pixbuf::from_file(&random_preview_entry).unwrap();
+            // the zoomed size follows the image's size
+            if viewport.zoom().is_some() {
+                viewport.set_zoom(viewport.zoom(), None);
+            }
+            preview_widget.queue_allocate();
+            update_rendered_watermark_preview();
+        }
+    });
 
-                preview_image_pixbuf = match preview_image_pixbuf.apply_embedded_orientation() {
-                    Some(image) => image,
-                    _ => preview_image_pixbuf,
-                };
-                let mut image_preview_dims = preview_image_dimensions.borrow_mut();
+    for combo_row in [
+        &geometry_rows.resize_combo_row,
+        &geometry_rows.reframe_combo_row,
+        &geometry_rows.aspect_ratio_combo_row,
+        &geometry_rows.gravity_combo_row,
+    ] {
+        combo_row.connect_selected_notify({
+            let geometry_rows = Rc::clone(&geometry_rows);
+            let update_preview_geometry = Rc::clone(&update_preview_geometry);
+            move |_| {
+                geometry_rows.update_visibility();
+                update_preview_geometry();
+            }
+        });
+    }
+
+    for spin_row in [&geometry_rows.width_spin_row, &geometry_rows.height_spin_row] {
+        spin_row.connect_value_notify({
+            let update_preview_geometry = Rc::clone(&update_preview_geometry);
+            move |_| update_preview_geometry()
+        });
+    }
+
+    geometry_rows.pad_color_button.connect_rgba_notify({
+        let update_preview_geometry = Rc::clone(&update_preview_geometry);
+        move |_| update_preview_geometry()
+    });
+
+    comparison.connect(&show_original_button, &main_window);
+
+    // leaving the exact preview brings back the image and the live overlays
+    exact_preview_button.connect_toggled({
+        let update_preview_geometry = Rc::clone(&update_preview_geometry);
+        move |_| update_preview_geometry()
+    });
+
+    // the format decides whether the exact preview is flattened onto the background color
+    output_rows.format_combo_row.connect_selected_notify({
+        let request_exact_preview = Rc::clone(&request_exact_preview);
+        move |_| request_exact_preview()
+    });
+
+    output_rows.background_color_button.connect_rgba_notify({
+        let request_exact_preview = Rc::clone(&request_exact_preview);
+        move |_| request_exact_preview()
+    });
+
+    for anchor_button in &position_rows.anchor_buttons {
+        anchor_button.connect_toggled({
+            let preview_widget = Rc::clone(&preview_widget);
+            let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+            move |anchor_button| {
+                // the previously active button of the group also reports its change
+                if anchor_button.is_active() {
+                    preview_widget.queue_allocate();
+                    update_rendered_watermark_preview();
+                }
+            }
+        });
+    }
+
+    for offset_spin_row in [&position_rows.offset_x_spin_row, &position_rows.offset_y_spin_row] {
+        offset_spin_row.connect_value_notify({
+            let preview_widget = Rc::clone(&preview_widget);
+            let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+            move |_| {
+                preview_widget.queue_allocate();
+                update_rendered_watermark_preview();
+            }
+        });
+    }
+
+    position_rows.offset_unit_toggle_group.connect_active_notify({
+        let preview_widget = Rc::clone(&preview_widget);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |_| {
+            preview_widget.queue_allocate();
+            update_rendered_watermark_preview();
+        }
+    });
+
+    for tiling_spin_row in [
+        &tiling_rows.horizontal_spacing_spin_row,
+        &tiling_rows.vertical_spacing_spin_row,
+        &tiling_rows.row_offset_spin_row,
+    ] {
+        tiling_spin_row.connect_value_notify({
+            let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+            move |_| update_rendered_watermark_preview()
+        });
+    }
+
+    scale_slider.connect_value_changed({
+        let preview_widget = Rc::clone(&preview_widget);        
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |_| {
+            let _ = &preview_widget.queue_allocate();
+            update_rendered_watermark_preview();
+        }
+    });
+
+    opacity_slider.connect_value_changed({
+        let watermark_preview = Rc::clone(&watermark_preview);        
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |opacity_slider| {
+            // the rendered preview has the opacity baked in
+            watermark_preview.set_opacity(opacity_slider.value() / 100.0);
+            update_rendered_watermark_preview();
+        }
+    });
+
+    blend_mode_combo_row.connect_selected_notify({
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |_| update_rendered_watermark_preview()
+    });
+
+    margin_spin_row.connect_value_notify({
+        let preview_widget = Rc::clone(&preview_widget);        
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |_| {
+            let _ = &preview_widget.queue_allocate();
+            update_rendered_watermark_preview();
+        }
+    });
+
+    rotation_slider.connect_value_changed({
+        let watermark_preview = Rc::clone(&watermark_preview);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let preview_widget = Rc::clone(&preview_widget);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        move |rotation_slider| {
+            if let Some(watermark_source) = preview_watermark_source.borrow().as_ref() {
+                set_rotated_watermark_preview(&watermark_preview, watermark_source, rotation_slider.value() as f32);
+            }
+            preview_widget.queue_allocate();
+            update_rendered_watermark_preview();
+        }
+    });
+
+
+    let loader_header_container = Box::builder()
+        .orientation(Orientation::Vertical)
+        .build();
+
+    let loader_navigation_page = NavigationPage::builder()
+    .child(&loader_header_container)
+    .title("Cliquemark")    
+    .build();
+
+    let loader_header = HeaderBar::builder()
+        .build();
+    loader_header.add_css_class("flat");
+    loader_header_container.append(&loader_header);
+
+    let loader_page_container = Box::builder()
+    .orientation(Orientation::Vertical)
+    .valign(Align::Center)
+    .halign(Align::Center)
+    .vexpand(true)
+    .build();
+    loader_header_container.append(&loader_page_container);
+    
+    main_stack.add_named(&loader_navigation_page, Some("loader_page"));
+
+    let watermark_loading_spinner = Spinner::builder()
+        .height_request(50)
+        .build();
+    loader_page_container.append(&watermark_loading_spinner);
+
+    let watermark_progress_bar = Rc::new(ProgressBar::builder()
+        .width_request(300)
+        .margin_top(30)
+        .show_text(true)
+        .build()
+    );
+    loader_page_container.append(&*watermark_progress_bar);
+
+    let loader_button_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .halign(Align::Center)
+        .spacing(12)
+        .margin_top(70)
+        .build();
+    loader_page_container.append(&loader_button_box);
+
+    let pause_button = ToggleButton::builder()
+        .label("Pause")
+        .build();
+    pause_button.add_css_class("pill");
+    loader_button_box.append(&pause_button);
+
+    let cancel_button = Button::builder()
+        .label("Cancel")
+        .build();
+    cancel_button.add_css_class("destructive-action");
+    cancel_button.add_css_class("pill");
+    loader_button_box.append(&cancel_button);
+
+    // replaced for every run, so a cancelled run can't affect the next one
+    let batch_control: Rc<RefCell<Arc<BatchControl>>> = Rc::new(RefCell::new(Arc::new(BatchControl::default())));
+
+    pause_button.connect_toggled({
+        let batch_control = Rc::clone(&batch_control);
+        move |pause_button| {
+            if pause_button.is_active() {
+                batch_control.borrow().pause();
+                pause_button.set_label("Resume");
+            } else {
+                batch_control.borrow().resume();
+                pause_button.set_label("Pause");
+            }
+        }
+    });
+
+    cancel_button.connect_clicked({
+        let batch_control = Rc::clone(&batch_control);
+        let pause_button = pause_button.clone();
+        move |cancel_button| {
+            batch_control.borrow().cancel();
+            cancel_button.set_sensitive(false);
+            cancel_button.set_label("Cancelling…");
+            pause_button.set_sensitive(false);
+        }
+    });
+
+
+    let results_header_container = Box::builder()
+        .orientation(Orientation::Vertical)
+        .build();
+
+    let results_navigation_page = NavigationPage::builder()
+        .child(&results_header_container)
+        .title("Cliquemark")
+        .build();
+
+    let results_header = HeaderBar::builder()
+        .build();
+    results_header.add_css_class("flat");
+    results_header_container.append(&results_header);
+
+    let results_toast_overlay = ToastOverlay::new();
+    results_toast_overlay.set_vexpand(true);
+    results_header_container.append(&results_toast_overlay);
+
+    main_stack.add_named(&results_navigation_page, Some("results_page"));
+
+    let results_page_container = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(12)
+        .margin_start(50)
+        .margin_end(50)
+        .margin_bottom(50)
+        .build();
+    results_toast_overlay.set_child(Some(&Clamp::builder()
+        .maximum_size(800)
+        .child(&results_page_container)
+        .build()
+    ));
+
+    let results_title_label = Label::builder()
+        .build();
+    results_title_label.add_css_class("title-1");
+    results_page_container.append(&results_title_label);
+
+    let results_summary_label = Label::builder()
+        .build();
+    results_summary_label.add_css_class("dim-label");
+    results_page_container.append(&results_summary_label);
+
+    let results_box = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(24)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    results_page_container.append(&ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .child(&results_box)
+        .build()
+    );
+
+    let results_button_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .halign(Align::Center)
+        .spacing(12)
+        .build();
+    results_page_container.append(&results_button_box);
+
+    let retry_failed_button = Button::builder()
+        .label("Retry failed")
+        .build();
+    retry_failed_button.add_css_class("suggested-action");
+    retry_failed_button.add_css_class("pill");
+    results_button_box.append(&retry_failed_button);
+
+    let copy_report_button = Button::builder()
+        .label("Copy error report")
+        .build();
+    copy_report_button.add_css_class("pill");
+    results_button_box.append(&copy_report_button);
+
+    let results_done_button = Button::builder()
+        .label("Done")
+        .build();
+    results_done_button.add_css_class("pill");
+    results_button_box.append(&results_done_button);
+
+    let results_page = ResultsPage {
+        title_label: results_title_label,
+        summary_label: results_summary_label,
+        results_box,
+        retry_failed_button,
+        copy_report_button,
+    };
+
+    // the last job with the latest result of each of its images, kept for retrying and the error report
+    let last_watermark_job: Rc<RefCell<Option<Arc<WatermarkJob>>>> = Rc::new(RefCell::new(None));
+    let last_results: Rc<RefCell<Vec<WatermarkResult>>> = Rc::new(RefCell::new(Vec::new()));
+
+    results_done_button.connect_clicked({
+        let main_stack = main_stack.clone();
+        move |_| main_stack.set_visible_child_full("main_page", StackTransitionType::Crossfade)
+    });
+
+    results_page.copy_report_button.connect_clicked({
+        let last_watermark_job = Rc::clone(&last_watermark_job);
+        let last_results = Rc::clone(&last_results);
+        let results_toast_overlay = results_toast_overlay.clone();
+        move |copy_report_button| {
+            let Some(watermark_job) = last_watermark_job.borrow().clone() else {
+                return;
+            };
+            copy_report_button.clipboard().set_text(&error_report(&watermark_job, &last_results.borrow()));
+            results_toast_overlay.add_toast(Toast::new("Error report copied"));
+        }
+    });
+
+    
+
+    // makes the folders and files the input and shows one of their images in the preview,
+    // an empty list clears the input
+    let load_input_paths = Rc::new({
+        let chosen_folder_text= Rc::clone(&chosen_folder_text);
+        let clear_input_button = clear_input_button.clone();
+        let toast_overlay = Rc::clone(&toast_overlay);
+
+        let chosen_inputs = Rc::clone(&chosen_inputs);
+        let input_entries = Rc::clone(&input_entries);
+        let unreadable_entries = Rc::clone(&unreadable_entries);
+        let recursive_check_button = Rc::clone(&recursive_check_button);
+        let image_preview = Rc::clone(&image_preview);
+        let original_preview = original_preview.clone();
+        let preview_pixbuf = Rc::clone(&preview_pixbuf);
+        let preview_source_image = Rc::clone(&preview_source_image);
+        let preview_entry = Rc::clone(&preview_entry);
+        let preview_image = Rc::clone(&preview_image);
+        let preview_image_dimensions = Rc::clone(&preview_image_dimensions);
+        let update_preview_geometry = Rc::clone(&update_preview_geometry);
+        let preview_browser_box = preview_browser_box.clone();
+        let filmstrip_names = filmstrip_names.clone();
+        let filmstrip_selection = filmstrip_selection.clone();
+        let thumbnail_cache = Rc::clone(&thumbnail_cache);
+        let settings = settings.clone();
+
+        move |mut input_paths: Vec<PathBuf>| {
+            input_paths.retain(|input_path| input_path.is_dir() || is_image_file(input_path));
+            unreadable_entries.borrow_mut().clear();
+
+            if input_paths.is_empty() {
+                chosen_inputs.borrow_mut().clear();
+                save_last_inputs(settings.as_ref(), &[]);
+                input_entries.borrow_mut().clear();
+                *preview_pixbuf.borrow_mut() = None;
+                *preview_source_image.borrow_mut() = None;
+                *preview_entry.borrow_mut() = None;
+                *preview_image.borrow_mut() = None;
+                *preview_image_dimensions.borrow_mut() = [0, 0];
+                image_preview.set_paintable(None::<&Texture>);
+                original_preview.set_paintable(None::<&Texture>);
+                filmstrip_names.splice(0, filmstrip_names.n_items(), &[]);
+                thumbnail_cache.borrow_mut().clear();
+                preview_browser_box.set_visible(false);
+                clear_input_button.set_sensitive(false);
+                chosen_folder_text.set_text("Nothing chosen");
+                update_preview_geometry();
+                return;
+            }
+
+            let entries = match collect_input_files(&input_paths, recursive_check_button.is_active()) {
+                Ok(entries) => entries,
+                Err(error) => {
+                    let toast_message = Toast::builder()
+                        .title(format!("{}.", error))
+                        .build();
+                    
+                    toast_overlay.add_toast(toast_message);
+                    return;
+                }
+            };
+            
+            clear_input_button.set_sensitive(true);
+            chosen_folder_text.set_text(&input_summary(&input_paths, entries.len()));
+            chosen_folder_text.set_position(-1);
+            save_last_inputs(settings.as_ref(), &input_paths);
+            *chosen_inputs.borrow_mut() = input_paths;
+
+            // the image in the preview stays when it's still part of the input, otherwise a random one is shown
+            let preview_index = preview_entry
+                .borrow()
+                .as_ref()
+                .and_then(|(preview_path, _)| entries.iter().position(|entry| entry == preview_path))
+                .unwrap_or_else(|| rand::random_range(0..entries.len()));
+            let image_names: Vec<String> = entries
+                .iter()
+                .map(|entry| entry.file_name().unwrap_or_default().to_string_lossy().to_string())
+                .collect();
+            *input_entries.borrow_mut() = entries;
+
+            // deselected first so choosing the same position still shows the image of the new input
+            filmstrip_selection.set_selected(gtk::INVALID_LIST_POSITION);
+            filmstrip_names.splice(0, filmstrip_names.n_items(), &image_names.iter().map(String::as_str).collect::<Vec<_>>());
+            preview_browser_box.set_visible(true);
+            filmstrip_selection.set_selected(preview_index as u32);
+        }
+    });
+
+    // shows the input image at `index` in the preview once it's decoded, only the last chosen image is shown,
+    // an image that can't be opened is skipped for the next one that can
+    let show_preview_entry = Rc::new({
+        let toast_overlay = Rc::clone(&toast_overlay);
+        let input_entries = Rc::clone(&input_entries);
+        let unreadable_entries = Rc::clone(&unreadable_entries);
+        let preview_pixbuf = Rc::clone(&preview_pixbuf);
+        let preview_source_image = Rc::clone(&preview_source_image);
+        let preview_entry = Rc::clone(&preview_entry);
+        let update_preview_geometry = Rc::clone(&update_preview_geometry);
+        let preview_position_label = preview_position_label.clone();
+        let filmstrip_selection = filmstrip_selection.clone();
+        let loading_indicator = loading_indicator.clone();
+        let preview_load_generation = Rc::new(Cell::new(0u64));
+
+        move |index: usize| {
+            let Some(image_entry) = input_entries.borrow().get(index).cloned() else {
+                return;
+            };
+            preview_position_label.set_label(&format!("{} of {}", index + 1, input_entries.borrow().len()));
+
+            let generation = preview_load_generation.get() + 1;
+            preview_load_generation.set(generation);
+            loading_indicator.start();
+
+            glib::spawn_future_local(glib::clone!(
+                #[strong]
+                toast_overlay,
+                #[strong]
+                input_entries,
+                #[strong]
+                unreadable_entries,
+                #[strong]
+                preview_pixbuf,
+                #[strong]
+                preview_source_image,
+                #[strong]
+                preview_entry,
+                #[strong]
+                update_preview_geometry,
+                #[strong]
+                filmstrip_selection,
+                #[strong]
+                loading_indicator,
+                #[strong]
+                preview_load_generation,
+                async move {
+                    let loaded_image = {
+                        let image_entry = image_entry.clone();
+                        gio::spawn_blocking(move || load_preview_image(&image_entry)).await
+                    };
+                    loading_indicator.finish();
+                    // a newer image was chosen or the input changed meanwhile
+                    if generation != preview_load_generation.get() || input_entries.borrow().get(index) != Some(&image_entry) {
+                        return;
+                    }
+
+                    let error = match loaded_image {
+                        Ok(Ok(loaded_image)) => {
+                            *preview_pixbuf.borrow_mut() = Some(loaded_image.pixbuf());
+                            *preview_source_image.borrow_mut() = Some(loaded_image.preview_source_image);
+                            *preview_entry.borrow_mut() = Some((image_entry, index));
+
+                            update_preview_geometry();
+                            return;
+                        }
+                        Ok(Err(error)) => error.to_string(),
+                        // the decoder gave up on the file by panicking
+                        Err(_) => "the file is damaged".to_string(),
+                    };
+                    toast_overlay.add_toast(Toast::new(&format!("Couldn't open {}: {}", image_entry.display(), error)));
+                    unreadable_entries.borrow_mut().insert(image_entry);
+
+                    let fallback_index = {
+                        let input_entries = input_entries.borrow();
+                        let unreadable_entries = unreadable_entries.borrow();
+                        (1..input_entries.len())
+                            .map(|step| (index + step) % input_entries.len())
+                            .find(|fallback_index| !unreadable_entries.contains(&input_entries[*fallback_index]))
+                    };
+                    match fallback_index {
+                        Some(fallback_index) => filmstrip_selection.set_selected(fallback_index as u32),
+                        None => toast_overlay.add_toast(Toast::new("None of the chosen images could be opened.")),
+                    }
+                }
+            ));
+        }
+    });
+
+    filmstrip_selection.connect_selected_notify({
+        let show_preview_entry = Rc::clone(&show_preview_entry);
+        let filmstrip_list_view = filmstrip_list_view.clone();
+        move |filmstrip_selection| {
+            let selected = filmstrip_selection.selected();
+            if selected != gtk::INVALID_LIST_POSITION {
+                filmstrip_list_view.scroll_to(selected, ListScrollFlags::NONE, None);
+                show_preview_entry(selected as usize);
+            }
+        }
+    });
+
+    // the previous and next buttons wrap around at the ends of the input
+    for (navigation_button, step) in [(&previous_image_button, -1), (&next_image_button, 1)] {
+        navigation_button.connect_clicked({
+            let filmstrip_selection = filmstrip_selection.clone();
+            move |_| {
+                let image_count = filmstrip_selection.n_items() as i64;
+                let selected = filmstrip_selection.selected();
+                if image_count == 0 || selected == gtk::INVALID_LIST_POSITION {
+                    return;
+                }
+                filmstrip_selection.set_selected((selected as i64 + step).rem_euclid(image_count) as u32);
+            }
+        });
+    }
+
+    shuffle_image_button.connect_clicked({
+        let filmstrip_selection = filmstrip_selection.clone();
+        move |_| {
+            let image_count = filmstrip_selection.n_items();
+            let selected = filmstrip_selection.selected();
+            if image_count < 2 {
+                return;
+            }
+            // any image but the current one
+            let random_index = rand::random_range(0..image_count - 1);
+            filmstrip_selection.set_selected(if random_index >= selected { random_index + 1 } else { random_index });
+        }
+    });
+
+    thumbnail_factory.connect_setup(|_, list_item| {
+        let thumbnail = Picture::builder()
+            .content_fit(gtk::ContentFit::Cover)
+            .width_request(THUMBNAIL_SIZE)
+            .height_request(THUMBNAIL_SIZE)
+            .build();
+        list_item.downcast_ref::<ListItem>().unwrap().set_child(Some(&thumbnail));
+    });
+
+    thumbnail_factory.connect_bind({
+        let input_entries = Rc::clone(&input_entries);
+        let thumbnail_cache = Rc::clone(&thumbnail_cache);
+
+        move |_, list_item| {
+            let list_item = list_item.downcast_ref::<ListItem>().unwrap();
+            let Some(thumbnail) = list_item.child().and_downcast::<Picture>() else {
+                return;
+            };
+            let Some(image_entry) = input_entries.borrow().get(list_item.position() as usize).cloned() else {
+                return;
+            };
+            thumbnail.set_tooltip_text(image_entry.file_name().map(|file_name| file_name.to_string_lossy()).as_deref());
+
+            if let Some(thumbnail_texture) = thumbnail_cache.borrow().get(&image_entry) {
+                thumbnail.set_paintable(Some(thumbnail_texture));
+                return;
+            }
+            thumbnail.set_paintable(None::<&Texture>);
+
+            glib::spawn_future_local(glib::clone!(
+                #[weak]
+                list_item,
+                #[weak]
+                thumbnail,
+                #[strong]
+                input_entries,
+                #[strong]
+                thumbnail_cache,
+                async move {
+                    // unreadable images keep an empty thumbnail, the preview reports them when chosen
+                    let Ok(thumbnail_texture) = load_thumbnail(&image_entry).await else {
+                        return;
+                    };
+                    thumbnail_cache.borrow_mut().insert(image_entry.clone(), thumbnail_texture.clone());
+
+                    // the item may have been recycled for another image while decoding
+                    if input_entries.borrow().get(list_item.position() as usize) == Some(&image_entry) {
+                        thumbnail.set_paintable(Some(&thumbnail_texture));
+                    }
+                }
+            ));
+        }
+    });
+
+    add_folders_button.connect_clicked({
+        let main_window = Rc::clone(&main_window);
+        let chosen_inputs = Rc::clone(&chosen_inputs);
+        let load_input_paths = Rc::clone(&load_input_paths);
+
+        move |_| {
+            let folder_dialog = FileDialog::builder()
+            .title("Add Folders")
+            .build();
 
-                image_preview_dims[0] = preview_image_pixbuf.width(); 
-                image_preview_dims[1] = preview_image_pixbuf.height();  
-                
-                image_preview.set_paintable( Some(&Texture::for_pixbuf(&preview_image_pixbuf)) );
-                  
+            let chosen_inputs = Rc::clone(&chosen_inputs);
+            let load_input_paths = Rc::clone(&load_input_paths);
+            
+            folder_dialog.select_multiple_folders(Some(&*main_window),None::<&gtk::gio::Cancellable>, 
+            move |result| {
+                if let Ok(folders) = result {
+                    let folder_paths = folders.iter::<gio::File>().filter_map(|folder| folder.ok()?.path());
+                    load_input_paths(with_added_paths(&chosen_inputs.borrow(), folder_paths));
+                }
             });
         }
     });
 
-    choose_watermark_button.connect_clicked(
-        {
+    add_images_button.connect_clicked({
         let main_window = Rc::clone(&main_window);
-        let chosen_watermark_text = Rc::clone(&chosen_watermark_text);
+        let chosen_inputs = Rc::clone(&chosen_inputs);
+        let load_input_paths = Rc::clone(&load_input_paths);
+
+        move |_| {
+            let image_filter = FileFilter::new();
+            image_filter.set_name(Some("Images"));
+            image_filter.add_mime_type("image/*");
+
+            let file_dialog = FileDialog::builder()
+            .title("Add Images")
+            .default_filter(&image_filter)
+            .build();
+
+            let chosen_inputs = Rc::clone(&chosen_inputs);
+            let load_input_paths = Rc::clone(&load_input_paths);
+            
+            file_dialog.open_multiple(Some(&*main_window),None::<&gtk::gio::Cancellable>, 
+            move |result| {
+                if let Ok(files) = result {
+                    let file_paths = files.iter::<gio::File>().filter_map(|file| file.ok()?.path());
+                    load_input_paths(with_added_paths(&chosen_inputs.borrow(), file_paths));
+                }
+            });
+        }
+    });
+
+    clear_input_button.connect_clicked({
+        let load_input_paths = Rc::clone(&load_input_paths);
+        move |_| load_input_paths(Vec::new())
+    });
+
+    // the sub-folders change which images the chosen folders hold
+    recursive_check_button.connect_toggled({
+        let chosen_inputs = Rc::clone(&chosen_inputs);
+        let load_input_paths = Rc::clone(&load_input_paths);
+        move |_| {
+            let input_paths = chosen_inputs.borrow().clone();
+            if !input_paths.is_empty() {
+                load_input_paths(input_paths);
+            }
+        }
+    });
+
+    // decodes the watermark image on a worker thread, choosing another watermark meanwhile discards the result
+    let watermark_load_generation = Rc::new(Cell::new(0u64));
+    let load_image_watermark_preview = Rc::new({
         let watermark_preview = Rc::clone(&watermark_preview);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let preview_widget = Rc::clone(&preview_widget);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        let rotation_slider = Rc::clone(&rotation_slider);
         let toast_overlay = Rc::clone(&toast_overlay);
+        let loading_indicator = loading_indicator.clone();
+        let watermark_load_generation = Rc::clone(&watermark_load_generation);
+
+        move |watermark_path: PathBuf| {
+            let generation = watermark_load_generation.get() + 1;
+            watermark_load_generation.set(generation);
+            loading_indicator.start();
+
+            glib::spawn_future_local(glib::clone!(
+                #[strong]
+                watermark_preview,
+                #[strong]
+                preview_watermark_dimensions,
+                #[strong]
+                preview_watermark_source,
+                #[strong]
+                preview_widget,
+                #[strong]
+                update_rendered_watermark_preview,
+                #[strong]
+                rotation_slider,
+                #[strong]
+                toast_overlay,
+                #[strong]
+                loading_indicator,
+                #[strong]
+                watermark_load_generation,
+                async move {
+                    let watermark_image = {
+                        let watermark_path = watermark_path.clone();
+                        gio::spawn_blocking(move || load_image(&watermark_path)).await
+                    };
+                    loading_indicator.finish();
+                    if generation != watermark_load_generation.get() {
+                        return;
+                    }
+
+                    let error = match watermark_image {
+                        Ok(Ok(watermark_image)) => {
+                            set_image_watermark_preview(watermark_image, &watermark_preview, &preview_watermark_dimensions, &preview_watermark_source, rotation_slider.value() as f32);
+                            None
+                        }
+                        Ok(Err(error)) => Some(error.to_string()),
+                        // the decoder gave up on the file by panicking
+                        Err(_) => Some("the file is damaged".to_string()),
+                    };
+                    if let Some(error) = error {
+                        clear_watermark_preview(&watermark_preview, &preview_watermark_dimensions, &preview_watermark_source);
+                        toast_overlay.add_toast(Toast::new(&format!("Couldn't open the watermark {}: {}", watermark_path.display(), error)));
+                    }
+                    update_rendered_watermark_preview();
+                    preview_widget.queue_allocate();
+                }
+            ));
+        }
+    });
+
+    // makes the image file the watermark and switches to the image source
+    let load_watermark_file = Rc::new({
+        let chosen_watermark_text = Rc::clone(&chosen_watermark_text);
+        let load_image_watermark_preview = Rc::clone(&load_image_watermark_preview);
+        let watermark_source_toggle_group = Rc::clone(&watermark_source_toggle_group);
+        let toast_overlay = Rc::clone(&toast_overlay);
+
+        move |file_path: &Path| {
+            if !is_image_file(file_path) {
+                let no_images_found_toast = Toast::builder()
+                    .title("This image format is not supported.")
+                    .build();
+        
+                toast_overlay.add_toast(no_images_found_toast);
+
+                return;
+            }
+
+            chosen_watermark_text.set_text(&file_path.to_string_lossy());    
+            chosen_watermark_text.set_position(-1);       
+
+            // switching the source reloads the preview when text was selected
+            if watermark_source_toggle_group.active() != 0 {
+                watermark_source_toggle_group.set_active(0);
+            } else {
+                load_image_watermark_preview(file_path.to_path_buf());
+            }
+        }
+    });
+
+    choose_watermark_button.connect_clicked(
+        {
+        let main_window = Rc::clone(&main_window);
+        let load_watermark_file = Rc::clone(&load_watermark_file);
 
         move |_| {
             let file_dialog = FileDialog::builder()
             .title("Select Watermark")
             .build();
 
-            let chosen_watermark_text = Rc::clone(&chosen_watermark_text); 
-            let watermark_preview = Rc::clone(&watermark_preview);   
-            let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
-            let preview_widget = Rc::clone(&preview_widget);
-            let toast_overlay = Rc::clone(&toast_overlay);
+            let load_watermark_file = Rc::clone(&load_watermark_file);
          
             file_dialog.open(Some(&*main_window),None::<&gtk::gio::Cancellable>, move |result| {
                 match result {
                     Ok(file) => {
-                        let file_path = &file.path().unwrap();
-
-                        if !is_image_file(&file_path) {
-                            let no_images_found_toast = Toast::builder()
-                                .title("This image format is not supported.")
-                                .build();
-                    
-                            toast_overlay.add_toast(no_images_found_toast);
-
-                            return;
+                        if let Some(file_path) = file.path() {
+                            load_watermark_file(&file_path);
                         }
-
-                        chosen_watermark_text.set_text(&file_path.to_str().unwrap());    
-                        chosen_watermark_text.set_position(-1);       
-                                     
-                        let mut preview_watermark_pixbuf = Pixbuf::from_file(&file_path).unwrap();
-
-                        let mut watermark_preview_dims = preview_watermark_dimensions.borrow_mut();
-                        watermark_preview_dims[0] = preview_watermark_pixbuf.width(); 
-                        watermark_preview_dims[1] = preview_watermark_pixbuf.height();  
-                        // watermark_preview.set_file( Some(&File::for_path(&file_path)) );
-
-                        preview_watermark_pixbuf = match preview_watermark_pixbuf.apply_embedded_orientation() {
-                            Some(image) => image,
-                            _ => preview_watermark_pixbuf,
-                        };
-                        watermark_preview.set_paintable( Some(&Texture::for_pixbuf(&preview_watermark_pixbuf)) );
                     }
                     Err(error) => {
                         println!("Error: {}", error);
                     }
                 }
             });
-            let _ = &preview_widget.queue_allocate();
         }
     });
 
-    
-    let (watermarking_state_sender, watermarking_state_receiver) = async_channel::bounded(1);
-    let (progress_sender, progress_receiver) = async_channel::bounded(1);
+    // files dropped from a file manager, where they land decides what they're used for
+    let file_drop_target = DropTarget::new(FileList::static_type(), DragAction::COPY);
+    file_drop_target.connect_drop({
+        let choose_watermark_button = choose_watermark_button.clone();
+        let chosen_watermark_text = Rc::clone(&chosen_watermark_text);
+        let load_input_paths = Rc::clone(&load_input_paths);
+        let load_watermark_file = Rc::clone(&load_watermark_file);
+        let toast_overlay = Rc::clone(&toast_overlay);
 
-    confirm_button.connect_clicked(move |_| {
+        move |file_drop_target, value, x, y| {
+            let Some(main_window) = file_drop_target.widget() else {
+                return false;
+            };
+            let dropped_paths: Vec<PathBuf> = value
+                .get::<FileList>()
+                .map(|file_list| file_list.files().iter().filter_map(|file| file.path()).collect())
+                .unwrap_or_default();
+            let Some(target) = main_window.pick(x, y, gtk::PickFlags::INSENSITIVE) else {
+                return false;
+            };
+            let is_inside = |widget: &gtk::Widget| target == *widget || target.is_ancestor(widget);
+
+            let rejection = if is_inside(choose_watermark_button.upcast_ref()) || is_inside(chosen_watermark_text.upcast_ref()) {
+                match dropped_paths.as_slice() {
+                    [watermark_path] if watermark_path.is_file() => {
+                        load_watermark_file(watermark_path);
+                        return true;
+                    }
+                    _ => "Drop a single image file to use as the watermark.",
+                }
+            } else if is_inside(toast_overlay.upcast_ref()) {
+                if dropped_paths.iter().any(|dropped_path| dropped_path.is_dir() || is_image_file(dropped_path)) {
+                    load_input_paths(dropped_paths);
+                    return true;
+                }
+                "Drop folders or image files to watermark them."
+            } else {
+                "Drop folders on the preview and watermarks on the watermark row."
+            };
 
+            toast_overlay.add_toast(Toast::new(rejection));
+            false
+        }
+    });
+    main_window.add_controller(file_drop_target);
+
+    // loads the preview of the chosen watermark file or renders the text
+    let update_watermark_source_preview = Rc::new({
+        let watermark_source_toggle_group = Rc::clone(&watermark_source_toggle_group);
+        let chosen_watermark_text = Rc::clone(&chosen_watermark_text);
+        let text_watermark_container = text_watermark_container.clone();
+        let text_watermark_rows = Rc::clone(&text_watermark_rows);
         let watermark_preview = Rc::clone(&watermark_preview);
-        let image_preview = Rc::clone(&image_preview);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let preview_widget = Rc::clone(&preview_widget);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        let rotation_slider = Rc::clone(&rotation_slider);
+        let load_image_watermark_preview = Rc::clone(&load_image_watermark_preview);
+        let watermark_load_generation = Rc::clone(&watermark_load_generation);
+
+        move || {
+            let text_source_active = watermark_source_toggle_group.active() == 1;
+            text_watermark_container.set_visible(text_source_active);
+
+            let watermark_path = PathBuf::from(chosen_watermark_text.text().as_str());
+            if !text_source_active && is_image_file(&watermark_path) {
+                load_image_watermark_preview(watermark_path);
+                return;
+            }
 
-        let chosen_folder = (&chosen_folder_text).text().to_string();
-        let chosen_watermark = (&chosen_watermark_text).text().to_string();
+            // a watermark image still loading would replace the text
+            watermark_load_generation.set(watermark_load_generation.get() + 1);
+            if text_source_active {
+                set_text_watermark_preview(&text_watermark_rows, &watermark_preview, &preview_watermark_dimensions, &preview_watermark_source, rotation_slider.value() as f32);
+            } else {
+                clear_watermark_preview(&watermark_preview, &preview_watermark_dimensions, &preview_watermark_source);
+            }
+            update_rendered_watermark_preview();
+            preview_widget.queue_allocate();
+        }
+    });
+
+    watermark_source_toggle_group.connect_active_notify({
+        let update_watermark_source_preview = Rc::clone(&update_watermark_source_preview);
+        move |_| update_watermark_source_preview()
+    });
+
+    // re-render the text preview whenever one of its settings changes
+    let update_text_watermark_preview = Rc::new({
+        let text_watermark_rows = Rc::clone(&text_watermark_rows);
+        let watermark_preview = Rc::clone(&watermark_preview);
+        let preview_watermark_dimensions = Rc::clone(&preview_watermark_dimensions);
+        let preview_watermark_source = Rc::clone(&preview_watermark_source);
+        let preview_widget = Rc::clone(&preview_widget);
+        let update_rendered_watermark_preview = Rc::clone(&update_rendered_watermark_preview);
+        let rotation_slider = Rc::clone(&rotation_slider);
 
-        let relative_margin_width = (&margin_spin_row).value() as f32 / preview_image_dimensions.borrow()[0] as f32;        
-        let relative_surface_area = watermark_preview.width() as f32 * watermark_preview.height() as f32 / (image_preview.width() as f32 * image_preview.height() as f32); 
+        move || {
+            set_text_watermark_preview(&text_watermark_rows, &watermark_preview, &preview_watermark_dimensions, &preview_watermark_source, rotation_slider.value() as f32);
+            update_rendered_watermark_preview();
+            preview_widget.queue_allocate();
+        }
+    });
 
-        let alignment = match &alignment_toggle_group.active() {
-            0 => [1, 0, 0, 0],
-            1 => [0, 1, 0, 0],
-            2 => [0, 0, 1, 0],
-            3 => [0, 0, 0, 1],
-            _ => [0, 0, 0, 1],
-        };
+    text_watermark_rows.text_entry_row.connect_changed({
+        let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+        move |_| update_text_watermark_preview()
+    });
+
+    text_watermark_rows.color_button.connect_rgba_notify({
+        let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+        move |_| update_text_watermark_preview()
+    });
+
+    text_watermark_rows.weight_spin_row.connect_value_notify({
+        let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+        move |_| update_text_watermark_preview()
+    });
+
+    font_dialog_button.connect_font_desc_notify({
+        let text_watermark_rows = Rc::clone(&text_watermark_rows);
+        let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+
+        move |font_dialog_button| {
+            let Some(family) = font_dialog_button.font_desc().and_then(|font_desc| font_desc.family()) else {
+                return;
+            };
+            text_watermark_rows.font_action_row.set_subtitle(&family);
+            *text_watermark_rows.font_source.borrow_mut() = FontSource::System(family.to_string());
+            update_text_watermark_preview();
+        }
+    });
+
+    font_file_button.connect_clicked({
+        let main_window = Rc::clone(&main_window);
+        let text_watermark_rows = Rc::clone(&text_watermark_rows);
+        let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+
+        move |_| {
+            let font_filter = FileFilter::new();
+            font_filter.set_name(Some("Fonts"));
+            font_filter.add_suffix("ttf");
+            font_filter.add_suffix("otf");
 
-        let watermarking_state_sender = watermarking_state_sender.clone();
-        let progress_sender = progress_sender.clone();
+            let file_dialog = FileDialog::builder()
+                .title("Select Font")
+                .default_filter(&font_filter)
+                .build();
+
+            let text_watermark_rows = Rc::clone(&text_watermark_rows);
+            let update_text_watermark_preview = Rc::clone(&update_text_watermark_preview);
+
+            file_dialog.open(Some(&*main_window), None::<&gtk::gio::Cancellable>, move |result| {
+                let Some(font_path) = result.ok().and_then(|file| file.path()) else {
+                    return;
+                };
+                let font_name = font_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                text_watermark_rows.font_action_row.set_subtitle(&font_name);
+                *text_watermark_rows.font_source.borrow_mut() = FontSource::File(font_path);
+                update_text_watermark_preview();
+            });
+        }
+    });
+
+
+    let preset_rows = PresetRows {
+        watermark_source_toggle_group: Rc::clone(&watermark_source_toggle_group),
+        chosen_watermark_text: Rc::clone(&chosen_watermark_text),
+        text_watermark_rows: Rc::clone(&text_watermark_rows),
+        font_dialog_button: font_dialog_button.clone(),
+        placement_toggle_group: Rc::clone(&placement_toggle_group),
+        position_rows: Rc::clone(&position_rows),
+        tiling_rows: Rc::clone(&tiling_rows),
+        scale_slider: Rc::clone(&scale_slider),
+        opacity_slider: Rc::clone(&opacity_slider),
+        rotation_slider: Rc::clone(&rotation_slider),
+        blend_mode_combo_row: blend_mode_combo_row.clone(),
+        margin_spin_row: Rc::clone(&margin_spin_row),
+    };
+
+    match load_presets(&presets_path()) {
+        Ok(presets) => *preset_list.presets.borrow_mut() = presets,
+        Err(error) => toast_overlay.add_toast(Toast::new(&format!("Couldn't load the presets: {}", error))),
+    }
+    preset_list.refresh(None);
+
+    preset_list.combo_row.connect_selected_notify({
+        let preset_list = preset_list.clone();
+        let preset_rows = preset_rows.clone();
+        let update_watermark_source_preview = Rc::clone(&update_watermark_source_preview);
         let toast_overlay = Rc::clone(&toast_overlay);
-    
-        // println!("{:?}", chosen_folder);
-        let path_buf = PathBuf::from(&chosen_folder); 
-        
-        let image_entries = match std::fs::read_dir(path_buf) {
-            Ok(entries) => entries,
-            Err(_e) => {
-                let toast_message = Toast::builder()
-                    .title("Failed to read folder.")
-                    .build();
-        
-                toast_overlay.add_toast(toast_message);        
+
+        move |_| {
+            if preset_list.refreshing.get() {
                 return;
             }
-        }.filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            if is_image_file(&path) {
-                return Some(path);
-            } else {
-                return None;
+            preset_list.update_actions();
+
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            let preset = preset_list.presets.borrow()[index].clone();
+            preset_rows.apply(&preset);
+            update_watermark_source_preview();
+
+            if !preset.text_source && !preset.watermark_file.exists() {
+                toast_overlay.add_toast(Toast::new(&format!("The watermark of “{}” can't be found.", preset.name)));
+            }
+        }
+    });
+
+    presets::connect_actions(&preset_list, &preset_rows, &main_window, &toast_overlay);
+
+
+    // remembers the input, the watermark and its placement for the next launch
+    if let Some(settings) = &settings {
+        settings.bind("scale", &scale_slider.adjustment(), "value").build();
+        settings.bind("margin", &*margin_spin_row, "value").build();
+
+        let anchor_name = settings.string("anchor");
+        if let Some(anchor_button) = Anchor::ALL
+            .iter()
+            .position(|anchor| anchor_setting(*anchor) == anchor_name)
+            .and_then(|index| position_rows.anchor_buttons.get(index))
+        {
+            anchor_button.set_active(true);
+        }
+        for (anchor, anchor_button) in Anchor::ALL.into_iter().zip(&position_rows.anchor_buttons) {
+            anchor_button.connect_toggled({
+                let settings = settings.clone();
+                move |anchor_button| {
+                    if anchor_button.is_active() {
+                        let _ = settings.set_string("anchor", &anchor_setting(anchor));
+                    }
+                }
+            });
+        }
+
+        // a remembered path that's gone is reported and kept, it may be on a drive that isn't connected
+        let remembered_inputs = settings.strv("last-inputs");
+        let (last_inputs, missing_inputs): (Vec<PathBuf>, Vec<PathBuf>) = remembered_inputs
+            .iter()
+            .map(|last_input| PathBuf::from(last_input.as_str()))
+            .partition(|last_input| last_input.exists());
+        match missing_inputs.as_slice() {
+            [] => {}
+            [missing_input] => toast_overlay.add_toast(Toast::new(&format!("The last input {} no longer exists.", missing_input.display()))),
+            _ => toast_overlay.add_toast(Toast::new(&format!("{} of the last inputs no longer exist.", missing_inputs.len()))),
+        }
+        if !last_inputs.is_empty() {
+            load_input_paths(last_inputs);
+            // loading saved only the existing paths
+            let _ = settings.set_strv("last-inputs", remembered_inputs);
+        }
+
+        let last_watermark = PathBuf::from(settings.string("last-watermark").as_str());
+        if last_watermark.is_file() {
+            chosen_watermark_text.set_text(&last_watermark.to_string_lossy());
+            chosen_watermark_text.set_position(-1);
+            update_watermark_source_preview();
+        } else if !last_watermark.as_os_str().is_empty() {
+            toast_overlay.add_toast(Toast::new(&format!("The last watermark {} no longer exists.", last_watermark.display())));
+        }
+
+        // saved whenever the entry changes, however the watermark was chosen
+        chosen_watermark_text.connect_changed({
+            let settings = settings.clone();
+            move |chosen_watermark_text| {
+                let watermark_path = PathBuf::from(chosen_watermark_text.text().as_str());
+                if is_image_file(&watermark_path) && watermark_path.is_file() {
+                    let _ = settings.set_string("last-watermark", &watermark_path.to_string_lossy());
+                }
+            }
+        });
+    }
+
+    
+    let (watermarking_state_sender, watermarking_state_receiver) = async_channel::bounded(1);
+    let (progress_sender, progress_receiver) = async_channel::bounded(1);
+    let (toast_sender, toast_receiver) = async_channel::bounded::<String>(1);
+    let (results_sender, results_receiver) = async_channel::bounded(1);
+    let watermarking_channels = WatermarkingChannels {
+        state: watermarking_state_sender,
+        progress: progress_sender,
+        toast: toast_sender,
+        results: results_sender,
+    };
+
+    // Queue the async block to show messages from the watermarking thread
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        toast_overlay,
+        async move {
+            while let Ok(toast_title) = toast_receiver.recv().await {
+                toast_overlay.add_toast(Toast::builder().title(toast_title).build());
+            }
+        }
+    ));
+
+    // a fresh control for every run, with the loader page buttons reset
+    let new_batch_control = Rc::new({
+        let batch_control = Rc::clone(&batch_control);
+        let pause_button = pause_button.clone();
+        let cancel_button = cancel_button.clone();
+        move || {
+            let new_batch_control = Arc::new(BatchControl::default());
+            *batch_control.borrow_mut() = Arc::clone(&new_batch_control);
+
+            pause_button.set_active(false);
+            pause_button.set_sensitive(true);
+            cancel_button.set_label("Cancel");
+            cancel_button.set_sensitive(true);
+            new_batch_control
+        }
+    });
+
+    results_page.retry_failed_button.connect_clicked({
+        let last_watermark_job = Rc::clone(&last_watermark_job);
+        let last_results = Rc::clone(&last_results);
+        let new_batch_control = Rc::clone(&new_batch_control);
+        let watermarking_channels = watermarking_channels.clone();
+        move |_| {
+            let Some(watermark_job) = last_watermark_job.borrow().clone() else {
+                return;
+            };
+            let failed = last_results
+                .borrow()
+                .iter()
+                .filter(|result| result.result.is_err() && !result.is_skipped())
+                // the results page lists the ones without an output path as not retried
+                .filter_map(|result| result.target.clone().map(|target| (result.source.clone(), target)))
+                .collect();
+
+            spawn_watermarking(watermark_job, Some(failed), new_batch_control(), watermarking_channels.clone());
+        }
+    });
+
+    // Queue the async block to show the results page, retried images replace their earlier result
+    glib::spawn_future_local(glib::clone!(
+        #[weak]
+        confirm_button,
+        #[weak]
+        main_stack,
+        #[strong]
+        last_watermark_job,
+        #[strong]
+        last_results,
+        async move {
+            while let Ok(results) = results_receiver.recv().await {
+                {
+                    let mut last_results = last_results.borrow_mut();
+                    for result in results {
+                        match last_results.iter_mut().find(|last_result| last_result.source == result.source) {
+                            Some(last_result) => *last_result = result,
+                            None => last_results.push(result),
+                        }
+                    }
+                    if let Some(watermark_job) = last_watermark_job.borrow().as_ref() {
+                        results_page.show(&watermark_job.target_folder, &last_results);
+                    }
+                }
+
+                confirm_button.set_sensitive(true);
+                main_stack.set_visible_child_full("results_page", StackTransitionType::Crossfade);
             }
-        }).collect::<Vec<_>>();
+        }
+    ));
+
+    confirm_button.connect_clicked(move |_| {
 
+        let chosen_watermark = (&chosen_watermark_text).text().to_string();
+
+        let toast_overlay = Rc::clone(&toast_overlay);
+    
+        // the images found when the input was chosen, the output mirrors where they came from
+        let image_entries = input_entries.borrow().clone();
         if image_entries.is_empty() {
             let toast_message = Toast::builder()
-                    .title("No images found.")
-                    .build();
-        
-                toast_overlay.add_toast(toast_message);
+                .title("No images found.")
+                .build();
+    
+            toast_overlay.add_toast(toast_message);        
             return;
         }
+        let Some(source_folder) = common_folder(&chosen_inputs.borrow()) else {
+            let toast_message = Toast::builder()
+                .title("The chosen folders and files have no folder in common to put the output in.")
+                .build();
+    
+            toast_overlay.add_toast(toast_message);        
+            return;
+        };
         
-        let mut watermark_decoder = match ImageReader::open(&chosen_watermark) {
-            Ok(reader) => reader.into_decoder().unwrap(),
-            Err(_e) => {
+        let watermark_source = match watermark_source_toggle_group.active() {
+            1 => text_watermark_rows.text_watermark().renderer().map(WatermarkSource::Text),
+            _ => load_image(&PathBuf::from(&chosen_watermark)).map(WatermarkSource::Image),
+        };
+        let watermark_source = match watermark_source {
+            Ok(source) => source,
+            Err(error) => {
+                let toast_title = match error {
+                    WatermarkError::Font(_) | WatermarkError::EmptyText => format!("{}.", error),
+                    _ => "No valid watermark selected.".to_string(),
+                };
                 let toast_message = Toast::builder()
-                        .title("No valid watermark selected.")
+                        .title(toast_title)
                         .build();
             
                     toast_overlay.add_toast(toast_message);
@@ -708,33 +3319,63 @@ fn build_ui(app: &Application) {
             }
         };
 
-        let watermark_orientation = match watermark_decoder.orientation() {
-            Ok(orientation) => orientation,
-            Err(_) => ImageOrientation::NoTransforms,
+        // the settings are relative to the preview image and the watermark, without them the batch has no watermark
+        let preview_missing = [*preview_image_dimensions.borrow(), *preview_watermark_dimensions.borrow()]
+            .iter()
+            .flatten()
+            .any(|length| *length <= 0);
+        if loading_indicator.is_loading() || preview_missing {
+            let toast_title = match loading_indicator.is_loading() {
+                true => "Wait until the preview has loaded.",
+                false => "The preview image or the watermark couldn't be loaded.",
+            };
+            let toast_message = Toast::builder()
+                .title(toast_title)
+                .build();
+
+            toast_overlay.add_toast(toast_message);
+            return;
+        }
+        let settings = export_settings();
+
+        let name_template = match output_rows.name_template() {
+            Ok(name_template) => name_template,
+            Err(error) => {
+                let toast_message = Toast::builder()
+                    .title(format!("{}.", error))
+                    .build();
+
+                toast_overlay.add_toast(toast_message);
+                return;
+            }
         };
-        let mut watermark_image = DynamicImage::from_decoder(watermark_decoder).unwrap();
-        watermark_image.apply_orientation(watermark_orientation);
-
-        let target_parent = PathBuf::from(&chosen_folder);
-        // target_parent.push("../");
-        let target_folder = create_target_folder(("watermarked").to_string(), target_parent).unwrap();
-
-        gio::spawn_blocking({
-            move || {
-                apply_watermark(
-                    relative_surface_area,
-                    image_entries,  
-                    watermark_image,
-                    &target_folder,
-                    relative_margin_width,
-                    alignment,
-                    watermarking_state_sender,
-                    progress_sender);
-                
-                let _ = opener::open(target_folder);
-                }
+
+        let target_folder = match create_target_folder(TARGET_FOLDER_NAME, &source_folder) {
+            Ok(folder) => folder,
+            Err(error) => {
+                let toast_message = Toast::builder()
+                        .title(format!("{}.", error))
+                        .build();
+            
+                    toast_overlay.add_toast(toast_message);
+                return;
             }
-        );
+        };
+
+        let watermark_job = Arc::new(WatermarkJob {
+            image_entries,
+            source_folder,
+            watermark: watermark_source,
+            target_folder,
+            geometry: geometry_rows.geometry(),
+            settings,
+            output: output_rows.output_settings(),
+            name_template,
+        });
+
+        *last_watermark_job.borrow_mut() = Some(Arc::clone(&watermark_job));
+        last_results.borrow_mut().clear();
+        spawn_watermarking(watermark_job, None, new_batch_control(), watermarking_channels.clone());
     });
 
     // Queue the async block to update the stack_page
@@ -760,114 +3401,11 @@ fn build_ui(app: &Application) {
         #[weak]
         watermark_progress_bar,
         async move {
-            while let Ok(progress_value) = progress_receiver.recv().await {
-                if progress_value == 0 {
-                    watermark_progress_bar.set_fraction(watermark_progress_bar.fraction() + watermark_progress_bar.pulse_step());
-                }
-                else {
-                    watermark_progress_bar.set_fraction(0.0);
-                }
+            while let Ok(progress_fraction) = progress_receiver.recv().await {
+                watermark_progress_bar.set_fraction(progress_fraction);
             }
         }
     ));
 
-    // cancel_button.connect_clicked(move |_| {
-    //     let _ = &main_stack.set_visible_child_full("main_page", gtk::StackTransitionType::Crossfade);
-    // });
-
     main_window.present();
 }
-
-fn apply_watermark( 
-    watermark_relative_surface_area:    f32,
-    image_entries:                      Vec<PathBuf>,
-    watermark_image:                    DynamicImage,
-    target_folder:                      &PathBuf,
-    relative_margin_width:              f32,
-    alignment:                          [i64; 4],
-    watermarking_state_sender:          async_channel::Sender<bool>, 
-    progress_sender:                    async_channel::Sender<i32>) {    
-    // TODO: SANITIZE INPUT BEFORE CALLING APPLY_WATERMARK
-    
-    watermarking_state_sender
-        .send_blocking(false)
-        .expect("The confirm channel needs to be open.");
-
-    progress_sender.send_blocking(1).expect("The progress channel needs to be open.");
-    let _results_array: Vec<Result<PathBuf, String>> = image_entries.into_par_iter().map(|image_entry| {
-        progress_sender.send_blocking(0).expect("The progress channel needs to be open.");
-        
-
-        let mut image_decoder = ImageReader::open(&image_entry).unwrap().into_decoder().unwrap();
-        let image_orientation = match image_decoder.orientation() {
-            Ok(orientation) => orientation,
-            Err(_) => ImageOrientation::NoTransforms,
-        };
-        let mut image = match DynamicImage::from_decoder(image_decoder) {
-            Ok(image) => image,
-            Err(error) => return Err(error.to_string()),
-        };
-        image.apply_orientation(image_orientation);
-
-        let watermark_surface_area = watermark_relative_surface_area * image.width() as f32 * image.height() as f32;
-        let watermark_aspect_ratio = watermark_image.width() as f32 / watermark_image.height() as f32;
-        
-        let watermark_scaled_width = (watermark_surface_area * watermark_aspect_ratio).sqrt().round() as i64;
-        let watermark_scaled_height: i64 = (watermark_surface_area as f32 / watermark_aspect_ratio as f32).sqrt().round() as i64; 
-        
-        let x_margin_scaled = (relative_margin_width * image.width() as f32).round() as i64;
-        let y_margin_scaled = x_margin_scaled;
-
-        let watermark_image_scaled = image::DynamicImage::ImageRgba8(imageops::resize(&watermark_image, watermark_scaled_width as u32, watermark_scaled_height as u32, Triangle));
-
-        let watermark_position_x = (alignment[1] + alignment[3]) * (image.width() as i64 - watermark_scaled_width - x_margin_scaled)
-                + (alignment[0] + alignment[2]) * x_margin_scaled;
-        let watermark_position_y = (alignment[2] + alignment[3]) * (image.height() as i64 - watermark_scaled_height - y_margin_scaled)
-                + (alignment[0] + alignment[1]) * y_margin_scaled;
-
-        imageops::overlay(&mut image, &watermark_image_scaled, watermark_position_x, watermark_position_y);
-        
-
-        let _ = image.save(target_folder.join(&image_entry.file_name().unwrap()));
-        return Ok(image_entry);
-    }).collect();
-
-    watermarking_state_sender
-        .send_blocking(true)
-        .expect("The confirm channel needs to be open.");
-}
-
-
-fn is_image_file(path: &std::path::Path) -> bool {
-    if let Some(extension) = path.extension() {
-        let ext = extension.to_string_lossy().to_lowercase();
-        matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "avif" | "ico")
-    } else {
-        false
-    }
-}
-
-fn create_target_folder(base_name: String, target_parent: PathBuf) -> Result<PathBuf, String> {
-    
-    let target_folder = target_parent.join(&base_name);
-    // println!("{:?}", target_parent);
-    if fs::create_dir(&target_folder).is_ok() {
-        return Ok(target_folder);
-    }
-    
-    let mut i = 1;
-    
-    loop {
-        let mut buf = base_name.clone();
-
-        buf.push(char::from_digit(i, 10).unwrap());
-
-        let target_folder = target_parent.join(&buf);
-
-        match fs::create_dir(&target_folder) {
-            Ok(()) => return Ok(target_folder),
-            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => i += 1,
-            Err(_e) => return Err(("Failed to create directory").to_string()),
-        }
-    }
-}
\ No newline at end of file
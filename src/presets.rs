@@ -0,0 +1,610 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use adw::{
+    prelude::*,
+    gdk::RGBA,
+    glib::{self, KeyFile, KeyFileFlags},
+    AlertDialog,
+    ApplicationWindow,
+    ComboRow,
+    ResponseAppearance,
+    SpinRow,
+    Toast,
+    ToastOverlay,
+    ToggleGroup,
+};
+use gtk::{
+    gio,
+    pango::FontDescription,
+    Entry,
+    FileDialog,
+    FileFilter,
+    FontDialogButton,
+    Scale,
+    StringList,
+};
+
+use cliquemark_core::{is_image_file, Anchor, BlendMode, FontSource};
+
+use crate::{PositionRows, TextWatermarkRows, TilingRows};
+
+// the presets are kept in the user's config folder, under the application's own folder
+const PRESETS_FOLDER_NAME: &str = "cliquemark";
+const PRESETS_FILE_NAME: &str = "presets.ini";
+
+// a named set of watermark settings, the values are stored the way the sidebar shows them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name:                   String,
+    pub text_source:            bool,
+    pub watermark_file:         PathBuf,
+    pub text:                   String,
+    pub font:                   FontSource,
+    pub color:                  RGBA,
+    pub weight:                 f64,
+    pub tiled:                  bool,
+    pub anchor:                 Anchor,
+    // in preview pixels, or in percent of the image when `offset_in_percent` is set
+    pub offset:                 [f64; 2],
+    pub offset_in_percent:      bool,
+    pub horizontal_spacing:     f64,
+    pub vertical_spacing:       f64,
+    pub row_offset:             f64,
+    pub scale:                  f64,
+    pub opacity:                f64,
+    pub rotation:               f64,
+    pub blend_mode:             BlendMode,
+    pub margin:                 f64,
+}
+
+impl Default for Preset {
+    // the settings the sidebar starts with
+    fn default() -> Self {
+        Preset {
+            name: String::new(),
+            text_source: false,
+            watermark_file: PathBuf::new(),
+            text: String::new(),
+            font: FontSource::default(),
+            color: RGBA::WHITE,
+            weight: 400.0,
+            tiled: false,
+            anchor: Anchor::default(),
+            offset: [0.0, 0.0],
+            offset_in_percent: false,
+            horizontal_spacing: 0.0,
+            vertical_spacing: 0.0,
+            row_offset: 0.0,
+            scale: 0.2,
+            opacity: 100.0,
+            rotation: 0.0,
+            blend_mode: BlendMode::default(),
+            margin: 0.0,
+        }
+    }
+}
+
+impl Preset {
+    fn write(&self, key_file: &KeyFile, group: &str) {
+        key_file.set_string(group, "name", &self.name);
+        key_file.set_string(group, "source", if self.text_source { "text" } else { "image" });
+        key_file.set_string(group, "watermark-file", &self.watermark_file.to_string_lossy());
+        key_file.set_string(group, "text", &self.text);
+        match &self.font {
+            FontSource::System(family) => key_file.set_string(group, "font-family", family),
+            FontSource::File(path) => key_file.set_string(group, "font-file", &path.to_string_lossy()),
+        }
+        key_file.set_string(group, "color", &self.color.to_str());
+        key_file.set_double(group, "weight", self.weight);
+        key_file.set_string(group, "placement", if self.tiled { "tiled" } else { "single" });
+        key_file.set_string(group, "anchor", self.anchor.name());
+        key_file.set_double(group, "offset-x", self.offset[0]);
+        key_file.set_double(group, "offset-y", self.offset[1]);
+        key_file.set_string(group, "offset-unit", if self.offset_in_percent { "%" } else { "px" });
+        key_file.set_double(group, "horizontal-spacing", self.horizontal_spacing);
+        key_file.set_double(group, "vertical-spacing", self.vertical_spacing);
+        key_file.set_double(group, "row-offset", self.row_offset);
+        key_file.set_double(group, "scale", self.scale);
+        key_file.set_double(group, "opacity", self.opacity);
+        key_file.set_double(group, "rotation", self.rotation);
+        key_file.set_string(group, "blend-mode", self.blend_mode.name());
+        key_file.set_double(group, "margin", self.margin);
+    }
+
+    // missing or unreadable values keep their default, so files of older versions still load
+    fn read(key_file: &KeyFile, group: &str) -> Preset {
+        let default = Preset::default();
+        let string = |key: &str| key_file.string(group, key).ok().map(|value| value.to_string());
+        let double = |key: &str, default: f64| key_file.double(group, key).unwrap_or(default);
+
+        let font = match (string("font-file"), string("font-family")) {
+            (Some(path), _) => FontSource::File(PathBuf::from(path)),
+            (None, Some(family)) => FontSource::System(family),
+            (None, None) => default.font,
+        };
+
+        Preset {
+            name: string("name").unwrap_or_else(|| group.to_string()),
+            text_source: string("source").is_some_and(|source| source == "text"),
+            watermark_file: string("watermark-file").map(PathBuf::from).unwrap_or_default(),
+            text: string("text").unwrap_or_default(),
+            font,
+            color: string("color").and_then(|color| RGBA::parse(color).ok()).unwrap_or(default.color),
+            weight: double("weight", default.weight),
+            tiled: string("placement").is_some_and(|placement| placement == "tiled"),
+            anchor: string("anchor")
+                .and_then(|name| Anchor::ALL.into_iter().find(|anchor| anchor.name() == name))
+                .unwrap_or(default.anchor),
+            offset: [double("offset-x", default.offset[0]), double("offset-y", default.offset[1])],
+            offset_in_percent: string("offset-unit").is_some_and(|unit| unit == "%"),
+            horizontal_spacing: double("horizontal-spacing", default.horizontal_spacing),
+            vertical_spacing: double("vertical-spacing", default.vertical_spacing),
+            row_offset: double("row-offset", default.row_offset),
+            scale: double("scale", default.scale),
+            opacity: double("opacity", default.opacity),
+            rotation: double("rotation", default.rotation),
+            blend_mode: string("blend-mode")
+                .and_then(|name| BlendMode::ALL.into_iter().find(|blend_mode| blend_mode.name() == name))
+                .unwrap_or(default.blend_mode),
+            margin: double("margin", default.margin),
+        }
+    }
+}
+
+pub fn presets_path() -> PathBuf {
+    glib::user_config_dir().join(PRESETS_FOLDER_NAME).join(PRESETS_FILE_NAME)
+}
+
+// presets of a file written by `save_presets` in their saved order, a missing file has none
+pub fn load_presets(path: &Path) -> Result<Vec<Preset>, glib::Error> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let key_file = KeyFile::new();
+    key_file.load_from_file(path, KeyFileFlags::NONE)?;
+    Ok(key_file.groups().iter().map(|group| Preset::read(&key_file, group.as_str())).collect())
+}
+
+// one group per preset, the names are stored as values so they can contain any character
+pub fn save_presets(path: &Path, presets: &[Preset]) -> Result<(), glib::Error> {
+    let key_file = KeyFile::new();
+    for (index, preset) in presets.iter().enumerate() {
+        preset.write(&key_file, &format!("Preset {}", index + 1));
+    }
+
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).map_err(|error| glib::Error::new(glib::FileError::Failed, &error.to_string()))?;
+    }
+    key_file.save_to_file(path)
+}
+
+// `name`, followed by a number if another preset than the one at `except` already has it
+pub fn unique_preset_name(presets: &[Preset], name: &str, except: Option<usize>) -> String {
+    let name = name.trim();
+    let taken = |candidate: &str| presets
+        .iter()
+        .enumerate()
+        .any(|(index, preset)| Some(index) != except && preset.name == candidate);
+
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..).map(|number| format!("{} ({})", name, number)).find(|candidate| !taken(candidate)).unwrap_or_default()
+}
+
+// every setting a preset stores, from the watermark source to its placement and appearance
+#[derive(Clone)]
+pub struct PresetRows {
+    pub watermark_source_toggle_group:  Rc<ToggleGroup>,
+    pub chosen_watermark_text:          Rc<Entry>,
+    pub text_watermark_rows:            Rc<TextWatermarkRows>,
+    pub font_dialog_button:             FontDialogButton,
+    pub placement_toggle_group:         Rc<ToggleGroup>,
+    pub position_rows:                  Rc<PositionRows>,
+    pub tiling_rows:                    Rc<TilingRows>,
+    pub scale_slider:                   Rc<Scale>,
+    pub opacity_slider:                 Rc<Scale>,
+    pub rotation_slider:                Rc<Scale>,
+    pub blend_mode_combo_row:           ComboRow,
+    pub margin_spin_row:                Rc<SpinRow>,
+}
+
+impl PresetRows {
+    pub fn preset(&self, name: &str) -> Preset {
+        let watermark_file = PathBuf::from(self.chosen_watermark_text.text().as_str());
+
+        Preset {
+            name: name.to_string(),
+            text_source: self.watermark_source_toggle_group.active() == 1,
+            // the entry reads "Nothing chosen" until a watermark is selected
+            watermark_file: if is_image_file(&watermark_file) { watermark_file } else { PathBuf::new() },
+            text: self.text_watermark_rows.text_entry_row.text().to_string(),
+            font: self.text_watermark_rows.font_source.borrow().clone(),
+            color: self.text_watermark_rows.color_button.rgba(),
+            weight: self.text_watermark_rows.weight_spin_row.value(),
+            tiled: self.placement_toggle_group.active() == 1,
+            anchor: self.position_rows.anchor(),
+            offset: [self.position_rows.offset_x_spin_row.value(), self.position_rows.offset_y_spin_row.value()],
+            offset_in_percent: self.position_rows.offset_unit_toggle_group.active() == 1,
+            horizontal_spacing: self.tiling_rows.horizontal_spacing_spin_row.value(),
+            vertical_spacing: self.tiling_rows.vertical_spacing_spin_row.value(),
+            row_offset: self.tiling_rows.row_offset_spin_row.value(),
+            scale: self.scale_slider.value(),
+            opacity: self.opacity_slider.value(),
+            rotation: self.rotation_slider.value(),
+            blend_mode: BlendMode::from_index(self.blend_mode_combo_row.selected()),
+            margin: self.margin_spin_row.value(),
+        }
+    }
+
+    // the rows update the preview through their own signals, except for the watermark file
+    pub fn apply(&self, preset: &Preset) {
+        if preset.watermark_file.as_os_str().is_empty() {
+            self.chosen_watermark_text.set_text("Nothing chosen");
+        } else {
+            self.chosen_watermark_text.set_text(&preset.watermark_file.to_string_lossy());
+            self.chosen_watermark_text.set_position(-1);
+        }
+
+        let text_watermark_rows = &self.text_watermark_rows;
+        text_watermark_rows.text_entry_row.set_text(&preset.text);
+        match &preset.font {
+            FontSource::System(family) => {
+                self.font_dialog_button.set_font_desc(&FontDescription::from_string(family));
+                text_watermark_rows.font_action_row.set_subtitle(family);
+            }
+            FontSource::File(font_path) => {
+                let font_name = font_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                text_watermark_rows.font_action_row.set_subtitle(&font_name);
+            }
+        }
+        *text_watermark_rows.font_source.borrow_mut() = preset.font.clone();
+        text_watermark_rows.color_button.set_rgba(&preset.color);
+        text_watermark_rows.weight_spin_row.set_value(preset.weight);
+
+        self.placement_toggle_group.set_active(preset.tiled as u32);
+        if let Some(anchor_button) = self.position_rows.anchor_buttons.get(preset.anchor.index() as usize) {
+            anchor_button.set_active(true);
+        }
+        self.position_rows.offset_unit_toggle_group.set_active(preset.offset_in_percent as u32);
+        self.position_rows.offset_x_spin_row.set_value(preset.offset[0]);
+        self.position_rows.offset_y_spin_row.set_value(preset.offset[1]);
+        self.tiling_rows.horizontal_spacing_spin_row.set_value(preset.horizontal_spacing);
+        self.tiling_rows.vertical_spacing_spin_row.set_value(preset.vertical_spacing);
+        self.tiling_rows.row_offset_spin_row.set_value(preset.row_offset);
+
+        self.scale_slider.set_value(preset.scale);
+        self.opacity_slider.set_value(preset.opacity);
+        self.rotation_slider.set_value(preset.rotation);
+        self.blend_mode_combo_row.set_selected(preset.blend_mode.index());
+        self.margin_spin_row.set_value(preset.margin);
+
+        self.watermark_source_toggle_group.set_active(preset.text_source as u32);
+    }
+}
+
+// the saved presets and the dropdown listing them, cloning only clones the references
+#[derive(Clone)]
+pub struct PresetList {
+    pub presets:        Rc<RefCell<Vec<Preset>>>,
+    pub names:          StringList,
+    pub combo_row:      ComboRow,
+    pub actions:        gio::SimpleActionGroup,
+    // set while the dropdown is refilled, so that doesn't apply a preset
+    pub refreshing:     Rc<Cell<bool>>,
+}
+
+impl PresetList {
+    pub fn selected(&self) -> Option<usize> {
+        let selected = self.combo_row.selected() as usize;
+        (selected < self.presets.borrow().len()).then_some(selected)
+    }
+
+    // lists the presets again with `selected` chosen, without applying it
+    pub fn refresh(&self, selected: Option<usize>) {
+        let presets = self.presets.borrow();
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+
+        self.refreshing.set(true);
+        self.names.splice(0, self.names.n_items(), &names);
+        self.combo_row.set_selected(selected.map_or(gtk::INVALID_LIST_POSITION, |index| index as u32));
+        self.refreshing.set(false);
+
+        self.combo_row.set_subtitle(if presets.is_empty() { "Save the current settings from the menu" } else { "" });
+        drop(presets);
+        self.update_actions();
+    }
+
+    // the actions on the chosen preset need one to be chosen
+    pub fn update_actions(&self) {
+        let has_selection = self.selected().is_some();
+        for action_name in ["save", "rename", "duplicate", "delete", "export"] {
+            let action = self.actions.lookup_action(action_name).and_then(|action| action.downcast::<gio::SimpleAction>().ok());
+            if let Some(action) = action {
+                action.set_enabled(has_selection);
+            }
+        }
+    }
+
+    // a failure is shown, the presets stay in memory until the next try
+    fn save(&self, toast_overlay: &ToastOverlay) {
+        if let Err(error) = save_presets(&presets_path(), &self.presets.borrow()) {
+            toast_overlay.add_toast(Toast::new(&format!("Couldn't save the presets: {}", error)));
+        }
+    }
+}
+
+// asks for a preset name, `on_name` gets it once confirmed
+fn ask_preset_name(
+    main_window:    &ApplicationWindow,
+    heading:        &str,
+    confirm_label:  &str,
+    initial_name:   &str,
+    on_name:        impl FnOnce(String) + 'static,
+) {
+    let name_entry = Entry::builder()
+        .text(initial_name)
+        .activates_default(true)
+        .build();
+
+    let name_dialog = AlertDialog::builder()
+        .heading(heading)
+        .extra_child(&name_entry)
+        .default_response("confirm")
+        .close_response("cancel")
+        .build();
+    name_dialog.add_responses(&[("cancel", "Cancel"), ("confirm", confirm_label)]);
+    name_dialog.set_response_appearance("confirm", ResponseAppearance::Suggested);
+    name_dialog.set_response_enabled("confirm", !initial_name.trim().is_empty());
+
+    name_entry.connect_changed(glib::clone!(
+        #[weak]
+        name_dialog,
+        move |name_entry| name_dialog.set_response_enabled("confirm", !name_entry.text().trim().is_empty())
+    ));
+
+    name_dialog.choose(main_window, None::<&gio::Cancellable>, move |response| {
+        if response == "confirm" {
+            on_name(name_entry.text().trim().to_string());
+        }
+    });
+}
+
+// the actions of the preset menu, they work on the preset chosen in the dropdown of `preset_list`
+pub fn connect_actions(
+    preset_list:    &PresetList,
+    preset_rows:    &PresetRows,
+    main_window:    &Rc<ApplicationWindow>,
+    toast_overlay:  &Rc<ToastOverlay>,
+) {
+    let new_preset_action = gio::SimpleAction::new("new", None);
+    new_preset_action.connect_activate({
+        let main_window = Rc::clone(main_window);
+        let preset_list = preset_list.clone();
+        let preset_rows = preset_rows.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let preset_list = preset_list.clone();
+            let preset_rows = preset_rows.clone();
+            let toast_overlay = Rc::clone(&toast_overlay);
+
+            ask_preset_name(&main_window, "New Preset", "Create", "", move |name| {
+                let index = {
+                    let mut presets = preset_list.presets.borrow_mut();
+                    let name = unique_preset_name(&presets, &name, None);
+                    presets.push(preset_rows.preset(&name));
+                    presets.len() - 1
+                };
+                preset_list.refresh(Some(index));
+                preset_list.save(&toast_overlay);
+            });
+        }
+    });
+
+    let save_preset_action = gio::SimpleAction::new("save", None);
+    save_preset_action.connect_activate({
+        let preset_list = preset_list.clone();
+        let preset_rows = preset_rows.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            let name = {
+                let mut presets = preset_list.presets.borrow_mut();
+                presets[index] = preset_rows.preset(&presets[index].name);
+                presets[index].name.clone()
+            };
+            preset_list.save(&toast_overlay);
+            toast_overlay.add_toast(Toast::new(&format!("Saved the settings to “{}”", name)));
+        }
+    });
+
+    let rename_preset_action = gio::SimpleAction::new("rename", None);
+    rename_preset_action.connect_activate({
+        let main_window = Rc::clone(main_window);
+        let preset_list = preset_list.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            let preset_list = preset_list.clone();
+            let toast_overlay = Rc::clone(&toast_overlay);
+            let current_name = preset_list.presets.borrow()[index].name.clone();
+
+            ask_preset_name(&main_window, "Rename Preset", "Rename", &current_name, move |name| {
+                {
+                    let mut presets = preset_list.presets.borrow_mut();
+                    presets[index].name = unique_preset_name(&presets, &name, Some(index));
+                }
+                preset_list.refresh(Some(index));
+                preset_list.save(&toast_overlay);
+            });
+        }
+    });
+
+    let duplicate_preset_action = gio::SimpleAction::new("duplicate", None);
+    duplicate_preset_action.connect_activate({
+        let preset_list = preset_list.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            {
+                let mut presets = preset_list.presets.borrow_mut();
+                let mut duplicate = presets[index].clone();
+                duplicate.name = unique_preset_name(&presets, &duplicate.name, None);
+                presets.insert(index + 1, duplicate);
+            }
+            // the copy has the same settings, so it's chosen without applying anything
+            preset_list.refresh(Some(index + 1));
+            preset_list.save(&toast_overlay);
+        }
+    });
+
+    let delete_preset_action = gio::SimpleAction::new("delete", None);
+    delete_preset_action.connect_activate({
+        let main_window = Rc::clone(main_window);
+        let preset_list = preset_list.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            let preset_list = preset_list.clone();
+            let toast_overlay = Rc::clone(&toast_overlay);
+
+            let delete_dialog = AlertDialog::builder()
+                .heading("Delete Preset?")
+                .body(format!("“{}” will be deleted, the current settings stay as they are.", preset_list.presets.borrow()[index].name))
+                .default_response("cancel")
+                .close_response("cancel")
+                .build();
+            delete_dialog.add_responses(&[("cancel", "Cancel"), ("delete", "Delete")]);
+            delete_dialog.set_response_appearance("delete", ResponseAppearance::Destructive);
+
+            delete_dialog.choose(&*main_window, None::<&gio::Cancellable>, move |response| {
+                if response != "delete" {
+                    return;
+                }
+                preset_list.presets.borrow_mut().remove(index);
+                preset_list.refresh(None);
+                preset_list.save(&toast_overlay);
+            });
+        }
+    });
+
+    let preset_file_filter = FileFilter::new();
+    preset_file_filter.set_name(Some("Presets"));
+    preset_file_filter.add_suffix("ini");
+
+    let import_presets_action = gio::SimpleAction::new("import", None);
+    import_presets_action.connect_activate({
+        let main_window = Rc::clone(main_window);
+        let preset_list = preset_list.clone();
+        let preset_file_filter = preset_file_filter.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let file_dialog = FileDialog::builder()
+                .title("Import Presets")
+                .default_filter(&preset_file_filter)
+                .build();
+
+            let preset_list = preset_list.clone();
+            let toast_overlay = Rc::clone(&toast_overlay);
+
+            file_dialog.open(Some(&*main_window), None::<&gtk::gio::Cancellable>, move |result| {
+                let Some(presets_file) = result.ok().and_then(|file| file.path()) else {
+                    return;
+                };
+                let imported_presets = match load_presets(&presets_file) {
+                    Ok(imported_presets) if !imported_presets.is_empty() => imported_presets,
+                    Ok(_) => {
+                        toast_overlay.add_toast(Toast::new("No presets found in this file."));
+                        return;
+                    }
+                    Err(error) => {
+                        toast_overlay.add_toast(Toast::new(&format!("Couldn't import the presets: {}", error)));
+                        return;
+                    }
+                };
+
+                // presets with a name that's already taken are numbered
+                let first_index = {
+                    let mut presets = preset_list.presets.borrow_mut();
+                    let first_index = presets.len();
+                    for mut imported_preset in imported_presets {
+                        imported_preset.name = unique_preset_name(&presets, &imported_preset.name, None);
+                        presets.push(imported_preset);
+                    }
+                    first_index
+                };
+                preset_list.refresh(None);
+                preset_list.save(&toast_overlay);
+                // chosen outside of the refresh so the first imported preset is applied
+                preset_list.combo_row.set_selected(first_index as u32);
+            });
+        }
+    });
+
+    let export_preset_action = gio::SimpleAction::new("export", None);
+    export_preset_action.connect_activate({
+        let main_window = Rc::clone(main_window);
+        let preset_list = preset_list.clone();
+        let preset_file_filter = preset_file_filter.clone();
+        let toast_overlay = Rc::clone(toast_overlay);
+
+        move |_, _| {
+            let Some(index) = preset_list.selected() else {
+                return;
+            };
+            let preset = preset_list.presets.borrow()[index].clone();
+
+            let file_dialog = FileDialog::builder()
+                .title("Export Preset")
+                .initial_name(format!("{}.ini", preset.name))
+                .default_filter(&preset_file_filter)
+                .build();
+
+            let toast_overlay = Rc::clone(&toast_overlay);
+
+            file_dialog.save(Some(&*main_window), None::<&gtk::gio::Cancellable>, move |result| {
+                let Some(presets_file) = result.ok().and_then(|file| file.path()) else {
+                    return;
+                };
+                let toast_title = match save_presets(&presets_file, &[preset]) {
+                    Ok(()) => "Preset exported".to_string(),
+                    Err(error) => format!("Couldn't export the preset: {}", error),
+                };
+                toast_overlay.add_toast(Toast::new(&toast_title));
+            });
+        }
+    });
+
+    for preset_action in [
+        new_preset_action,
+        save_preset_action,
+        rename_preset_action,
+        duplicate_preset_action,
+        delete_preset_action,
+        import_presets_action,
+        export_preset_action,
+    ] {
+        preset_list.actions.add_action(&preset_action);
+    }
+    preset_list.update_actions();
+}
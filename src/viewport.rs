@@ -0,0 +1,859 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use adw::{
+    prelude::*,
+    gdk::{Key, ModifierType, Rectangle},
+    glib,
+    ApplicationWindow,
+    SpinRow,
+    Toggle,
+    ToggleGroup,
+};
+use gtk::{
+    Adjustment,
+    Align,
+    Box,
+    Button,
+    EventControllerKey,
+    EventControllerMotion,
+    EventControllerScroll,
+    EventControllerScrollFlags,
+    Fixed,
+    GestureClick,
+    GestureDrag,
+    GestureZoom,
+    Label,
+    Orientation,
+    Overlay,
+    Picture,
+    PolicyType,
+    Scale,
+    ScrolledWindow,
+    ToggleButton,
+};
+
+use cliquemark_core::Anchor;
+
+use crate::PositionRows;
+
+// run after every zoom change
+type ZoomChangedHandlers = RefCell<Vec<std::boxed::Box<dyn Fn()>>>;
+
+// screen pixels per image pixel the preview can be zoomed in to
+const MAX_PREVIEW_ZOOM: f64 = 8.0;
+// zoom change of one Ctrl+scroll step
+const PREVIEW_ZOOM_STEP: f64 = 1.25;
+// width of the grabbable area around the comparison divider
+const COMPARISON_DIVIDER_WIDTH: i32 = 24;
+// side of the resize handles at the corners of the watermark in the preview
+const WATERMARK_HANDLE_SIZE: i32 = 12;
+// distance in preview pixels within which a dragged watermark snaps to a guide
+const SNAP_DISTANCE: f64 = 8.0;
+
+// moves a `length` long side starting at `start` so one of its edges or its centre lies on the nearest guide
+// of the `canvas`: its edges, centre or thirds, also returns that guide
+fn snap_to_guides(start: f64, length: f64, canvas: f64) -> (f64, Option<f64>) {
+    let guides = [0.0, canvas / 3.0, canvas / 2.0, canvas * 2.0 / 3.0, canvas];
+    let nearest = guides
+        .into_iter()
+        .flat_map(|guide| [0.0, length / 2.0, length].map(|edge| (guide - (start + edge), guide)))
+        .filter(|(distance, _)| distance.abs() <= SNAP_DISTANCE)
+        .min_by(|(distance, _), (other_distance, _)| distance.abs().total_cmp(&other_distance.abs()));
+
+    match nearest {
+        Some((distance, guide)) => (start + distance, Some(guide)),
+        None => (start, None),
+    }
+}
+
+// scrolls so the point at `fraction` of the content ends up at `view_fraction` of the visible part,
+// false while the adjustment doesn't span the content's new length yet
+fn scroll_to_fraction(adjustment: &Adjustment, content_length: f64, fraction: f64, view_fraction: f64) -> bool {
+    let page_size = adjustment.page_size();
+    if page_size <= 0.0 || (adjustment.upper() - content_length.max(page_size)).abs() > 1.0 {
+        return false;
+    }
+
+    let max_value = (adjustment.upper() - page_size).max(0.0);
+    adjustment.set_value((fraction * content_length - view_fraction * page_size).clamp(0.0, max_value));
+    true
+}
+
+// the preview fitted into the pane, or zoomed in a scrolled window that's panned by dragging,
+// cloning only clones the references
+#[derive(Clone)]
+pub struct PreviewViewport {
+    // holds the fitted preview or the scrollable zoomed one, and takes the zoom and pan gestures of both
+    pub area:                       Box,
+    pub zoom_toggle_group:          ToggleGroup,
+    pub zoom_label:                 Label,
+    pub jump_to_watermark_button:   Button,
+    fitted_box:                     Box,
+    fitted_parent:                  Box,
+    // takes the place of the fitted box while zoomed in, the preview is moved into it
+    zoom_container:                 Fixed,
+    scroller:                       ScrolledWindow,
+    preview_widget:                 Rc<Overlay>,
+    image_preview:                  Rc<Picture>,
+    image_dimensions:               Rc<RefCell<[i32; 2]>>,
+    // None fits the preview into the pane, otherwise the number of screen pixels per image pixel
+    zoom:                           Rc<Cell<Option<f64>>>,
+    // where each scrollbar goes once the zoomed preview is laid out: content length, content fraction, view fraction
+    scroll_targets:                 Rc<[Cell<Option<[f64; 3]>>; 2]>,
+    // keeps the zoom buttons from zooming again while they're updated
+    zoom_toggle_guard:              Rc<Cell<bool>>,
+    zoom_changed_handlers:          Rc<ZoomChangedHandlers>,
+}
+
+impl PreviewViewport {
+    // `preview_widget` is shown in `fitted_parent`, a child of `fitted_box`, while the preview is fitted
+    pub fn new(
+        fitted_box:         &Box,
+        fitted_parent:      &Box,
+        preview_widget:     &Rc<Overlay>,
+        image_preview:      &Rc<Picture>,
+        image_dimensions:   &Rc<RefCell<[i32; 2]>>,
+    ) -> PreviewViewport {
+        let area = Box::builder()
+            .orientation(Orientation::Vertical)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        area.append(fitted_box);
+
+        let zoom_container = Fixed::builder()
+            .halign(Align::Center)
+            .valign(Align::Center)
+            .build();
+        let scroller = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Automatic)
+            .vscrollbar_policy(PolicyType::Automatic)
+            .hexpand(true)
+            .vexpand(true)
+            .visible(false)
+            .child(&zoom_container)
+            .build();
+        scroller.set_cursor_from_name(Some("grab"));
+        area.append(&scroller);
+
+        // 100% shows one image pixel per screen pixel, Ctrl+scroll and pinching zoom in between
+        let zoom_toggle_group = ToggleGroup::builder()
+            .tooltip_text("Zoom, also with Ctrl+scroll or by pinching")
+            .build();
+        for (name, label) in [("fit", "Fit"), ("100", "100%"), ("200", "200%")] {
+            zoom_toggle_group.add(Toggle::builder().name(name).label(label).build());
+        }
+        zoom_toggle_group.set_active(0);
+
+        let zoom_label = Label::builder()
+            .width_chars(5)
+            .build();
+        zoom_label.add_css_class("dim-label");
+        zoom_label.add_css_class("numeric");
+
+        let jump_to_watermark_button = Button::builder()
+            .icon_name("find-location-symbolic")
+            .tooltip_text("Show the watermark at 100%")
+            .build();
+
+        PreviewViewport {
+            area,
+            zoom_toggle_group,
+            zoom_label,
+            jump_to_watermark_button,
+            fitted_box: fitted_box.clone(),
+            fitted_parent: fitted_parent.clone(),
+            zoom_container,
+            scroller,
+            preview_widget: Rc::clone(preview_widget),
+            image_preview: Rc::clone(image_preview),
+            image_dimensions: Rc::clone(image_dimensions),
+            zoom: Rc::new(Cell::new(None)),
+            scroll_targets: Rc::new([Cell::new(None), Cell::new(None)]),
+            zoom_toggle_guard: Rc::new(Cell::new(false)),
+            zoom_changed_handlers: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // None while the preview is fitted into the pane
+    pub fn zoom(&self) -> Option<f64> {
+        self.zoom.get()
+    }
+
+    // the zoom the preview is shown at, also while it's fitted
+    pub fn current_zoom(&self) -> f64 {
+        let image_width = self.image_dimensions.borrow()[0];
+        match self.zoom.get() {
+            Some(zoom) => zoom,
+            None if image_width > 0 => self.image_preview.width() as f64 * self.image_preview.scale_factor() as f64 / image_width as f64,
+            None => 1.0,
+        }
+    }
+
+    // `handler` runs after every zoom change, also when the zoomed size follows a new image size
+    pub fn connect_zoom_changed(&self, handler: impl Fn() + 'static) {
+        self.zoom_changed_handlers.borrow_mut().push(std::boxed::Box::new(handler));
+    }
+
+    // zooms keeping the point at `focus` of the image at `view_fraction` of the pane, None keeps the middle of the view
+    pub fn set_zoom(&self, zoom: Option<f64>, focus: Option<([f64; 2], [f64; 2])>) {
+        let [image_width, image_height] = *self.image_dimensions.borrow();
+        let zoom = zoom.filter(|_| image_width > 0 && image_height > 0);
+        let adjustments = [self.scroller.hadjustment(), self.scroller.vadjustment()];
+        let (focus, view_fraction) = focus.unwrap_or_else(|| {
+            let view_center = adjustments.clone().map(|adjustment| match adjustment.upper() {
+                upper if self.zoom.get().is_some() && upper > 0.0 => (adjustment.value() + adjustment.page_size() / 2.0) / upper,
+                _ => 0.5,
+            });
+            (view_center, [0.5, 0.5])
+        });
+        self.zoom.set(zoom);
+
+        self.zoom_toggle_guard.set(true);
+        self.zoom_toggle_group.set_active_name(match zoom {
+            None => Some("fit"),
+            Some(1.0) => Some("100"),
+            Some(2.0) => Some("200"),
+            Some(_) => None,
+        });
+        self.zoom_toggle_guard.set(false);
+        self.zoom_label.set_label(&zoom.map(|zoom| format!("{:.0}%", zoom * 100.0)).unwrap_or_default());
+
+        let preview_widget = &*self.preview_widget;
+        let in_zoom_container = preview_widget.parent().is_some_and(|parent| &parent == self.zoom_container.upcast_ref::<gtk::Widget>());
+        match zoom {
+            None => {
+                self.scroll_targets.iter().for_each(|scroll_target| scroll_target.set(None));
+                if in_zoom_container {
+                    self.zoom_container.remove(preview_widget);
+                    self.fitted_parent.append(preview_widget);
+                }
+                preview_widget.set_size_request(-1, -1);
+                self.scroller.set_visible(false);
+                self.fitted_box.set_visible(true);
+            }
+            Some(zoom) => {
+                let scale_factor = preview_widget.scale_factor() as f64;
+                let [content_width, content_height] = [image_width, image_height].map(|length| (length as f64 * zoom / scale_factor).round().max(1.0));
+                if !in_zoom_container {
+                    self.fitted_parent.remove(preview_widget);
+                    self.zoom_container.put(preview_widget, 0.0, 0.0);
+                }
+                preview_widget.set_size_request(content_width as i32, content_height as i32);
+                self.fitted_box.set_visible(false);
+                self.scroller.set_visible(true);
+
+                // the fixed container also holds the preview's top margin
+                let content_lengths = [content_width, content_height + preview_widget.margin_top() as f64];
+                for (axis, adjustment) in adjustments.iter().enumerate() {
+                    let scroll_target = [content_lengths[axis], focus[axis], view_fraction[axis]];
+                    let scrolled = scroll_to_fraction(adjustment, scroll_target[0], scroll_target[1], scroll_target[2]);
+                    self.scroll_targets[axis].set((!scrolled).then_some(scroll_target));
+                }
+            }
+        }
+
+        preview_widget.queue_allocate();
+        for handler in self.zoom_changed_handlers.borrow().iter() {
+            handler();
+        }
+    }
+
+    // shows `rectangle` of the preview, in preview pixels, in the middle of the pane at one image pixel per screen pixel
+    fn show_at_actual_size(&self, rectangle: Rectangle) {
+        let [display_width, display_height] = [self.image_preview.width() as f64, self.image_preview.height() as f64];
+        if display_width <= 0.0 || display_height <= 0.0 || rectangle.width() <= 0 {
+            return;
+        }
+
+        let focus = [
+            ((rectangle.x() as f64 + rectangle.width() as f64 / 2.0) / display_width).clamp(0.0, 1.0),
+            ((rectangle.y() as f64 + rectangle.height() as f64 / 2.0) / display_height).clamp(0.0, 1.0),
+        ];
+        self.set_zoom(Some(1.0), Some((focus, [0.5, 0.5])));
+    }
+
+    // the image and pane fractions of a point in the area, for zooming around it
+    fn area_focus(&self, [x, y]: [f64; 2]) -> ([f64; 2], [f64; 2]) {
+        let point = gtk::graphene::Point::new(x as f32, y as f32);
+        let fraction = |widget: &gtk::Widget| self.area
+            .compute_point(widget, &point)
+            .map(|point| [
+                (point.x() as f64 / widget.width().max(1) as f64).clamp(0.0, 1.0),
+                (point.y() as f64 / widget.height().max(1) as f64).clamp(0.0, 1.0),
+            ]);
+
+        let focus = fraction(self.preview_widget.upcast_ref()).unwrap_or([0.5, 0.5]);
+        let view_fraction = match self.zoom.get() {
+            Some(_) => fraction(self.scroller.upcast_ref()).unwrap_or(focus),
+            None => focus,
+        };
+        (focus, view_fraction)
+    }
+
+    // zooming out past the size that fits the pane goes back to fitting it
+    fn zoom_by(&self, factor: f64, focus: ([f64; 2], [f64; 2])) {
+        let [image_width, image_height] = *self.image_dimensions.borrow();
+        if image_width <= 0 || image_height <= 0 {
+            return;
+        }
+
+        let fitted_zoom = match self.zoom.get() {
+            None => self.current_zoom(),
+            Some(_) => (self.scroller.width() as f64 / image_width as f64)
+                .min(self.scroller.height() as f64 / image_height as f64)
+                * self.scroller.scale_factor() as f64,
+        };
+        let zoom = self.current_zoom() * factor;
+        if zoom <= fitted_zoom {
+            if self.zoom.get().is_some() {
+                self.set_zoom(None, None);
+            }
+            return;
+        }
+        self.set_zoom(Some(zoom.min(MAX_PREVIEW_ZOOM)), Some(focus));
+    }
+
+    // the zoom buttons, Ctrl+scroll, pinching, panning the zoomed preview by dragging it and jumping to the watermark
+    // in its box given by `watermark_rectangle`
+    pub fn connect(&self, watermark_rectangle: Rc<dyn Fn() -> Rectangle>) {
+        for (axis, adjustment) in [self.scroller.hadjustment(), self.scroller.vadjustment()].into_iter().enumerate() {
+            adjustment.connect_changed({
+                let scroll_targets = Rc::clone(&self.scroll_targets);
+                move |adjustment| {
+                    if let Some([content_length, fraction, view_fraction]) = scroll_targets[axis].get() {
+                        if scroll_to_fraction(adjustment, content_length, fraction, view_fraction) {
+                            scroll_targets[axis].set(None);
+                        }
+                    }
+                }
+            });
+        }
+
+        self.zoom_toggle_group.connect_active_name_notify({
+            let viewport = self.clone();
+            move |zoom_toggle_group| {
+                if viewport.zoom_toggle_guard.get() {
+                    return;
+                }
+                let zoom = match zoom_toggle_group.active_name().as_deref() {
+                    Some("100") => Some(1.0),
+                    Some("200") => Some(2.0),
+                    _ => None,
+                };
+                viewport.set_zoom(zoom, None);
+            }
+        });
+
+        // the pointer position in the area, where Ctrl+scroll zooms around
+        let pointer: Rc<Cell<Option<[f64; 2]>>> = Rc::new(Cell::new(None));
+        let motion_controller = EventControllerMotion::new();
+        motion_controller.connect_motion({
+            let pointer = Rc::clone(&pointer);
+            move |_, x, y| pointer.set(Some([x, y]))
+        });
+        motion_controller.connect_leave({
+            let pointer = Rc::clone(&pointer);
+            move |_| pointer.set(None)
+        });
+        self.area.add_controller(motion_controller);
+
+        // scrolling without Ctrl is left to the scrolled window
+        let scroll_controller = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL);
+        scroll_controller.connect_scroll({
+            let viewport = self.clone();
+            let pointer = Rc::clone(&pointer);
+            move |scroll_controller, _, dy| {
+                if !scroll_controller.current_event_state().contains(ModifierType::CONTROL_MASK) {
+                    return glib::Propagation::Proceed;
+                }
+                let pointer = pointer.get().unwrap_or([viewport.area.width() as f64 / 2.0, viewport.area.height() as f64 / 2.0]);
+                viewport.zoom_by(PREVIEW_ZOOM_STEP.powf(-dy), viewport.area_focus(pointer));
+                glib::Propagation::Stop
+            }
+        });
+        self.area.add_controller(scroll_controller);
+
+        // pinching scales the zoom the gesture started at
+        let pinch_start_zoom = Rc::new(Cell::new(1.0));
+        let zoom_gesture = GestureZoom::new();
+        zoom_gesture.connect_begin({
+            let viewport = self.clone();
+            let pinch_start_zoom = Rc::clone(&pinch_start_zoom);
+            move |_, _| pinch_start_zoom.set(viewport.current_zoom())
+        });
+        zoom_gesture.connect_scale_changed({
+            let viewport = self.clone();
+            let pinch_start_zoom = Rc::clone(&pinch_start_zoom);
+            move |zoom_gesture, scale| {
+                let Some((x, y)) = zoom_gesture.bounding_box_center() else {
+                    return;
+                };
+                viewport.zoom_by(pinch_start_zoom.get() * scale / viewport.current_zoom(), viewport.area_focus([x, y]));
+            }
+        });
+        self.area.add_controller(zoom_gesture);
+
+        // dragging the zoomed preview pans it
+        let pan_start = Rc::new(Cell::new([0.0, 0.0]));
+        let pan_gesture = GestureDrag::new();
+        pan_gesture.connect_drag_begin({
+            let pan_start = Rc::clone(&pan_start);
+            let scroller = self.scroller.clone();
+            move |_, _, _| {
+                pan_start.set([scroller.hadjustment().value(), scroller.vadjustment().value()]);
+                scroller.set_cursor_from_name(Some("grabbing"));
+            }
+        });
+        pan_gesture.connect_drag_update({
+            let pan_start = Rc::clone(&pan_start);
+            let scroller = self.scroller.clone();
+            move |_, dx, dy| {
+                let [start_x, start_y] = pan_start.get();
+                scroller.hadjustment().set_value(start_x - dx);
+                scroller.vadjustment().set_value(start_y - dy);
+            }
+        });
+        pan_gesture.connect_drag_end({
+            let scroller = self.scroller.clone();
+            move |_, _, _| scroller.set_cursor_from_name(Some("grab"))
+        });
+        self.scroller.add_controller(pan_gesture);
+
+        // shows the watermark's area at one image pixel per screen pixel
+        self.jump_to_watermark_button.connect_clicked({
+            let viewport = self.clone();
+            move |_| viewport.show_at_actual_size(watermark_rectangle())
+        });
+    }
+}
+
+// the original image left of a draggable divider over the preview, or over all of it while it's held,
+// cloning only clones the references
+#[derive(Clone)]
+pub struct PreviewComparison {
+    compare_button:     ToggleButton,
+    original_preview:   Picture,
+    // clips the full size picture to the part left of the divider
+    original_clip:      ScrolledWindow,
+    divider:            Box,
+    preview_widget:     Rc<Overlay>,
+    image_preview:      Rc<Picture>,
+    // where the divider is as a fraction of the image width
+    split:              Rc<Cell<f64>>,
+    // whether the original is held to cover the whole image
+    held:               Rc<Cell<bool>>,
+}
+
+impl PreviewComparison {
+    // adds the original and the divider as overlays of `preview_widget`
+    pub fn new(
+        compare_button:     &ToggleButton,
+        original_preview:   &Picture,
+        preview_widget:     &Rc<Overlay>,
+        image_preview:      &Rc<Picture>,
+    ) -> PreviewComparison {
+        let original_clip = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::External)
+            .vscrollbar_policy(PolicyType::External)
+            .can_target(false)
+            .visible(false)
+            .child(original_preview)
+            .build();
+        preview_widget.add_overlay(&original_clip);
+
+        let divider = Box::builder()
+            .orientation(Orientation::Vertical)
+            .width_request(COMPARISON_DIVIDER_WIDTH)
+            .visible(false)
+            .build();
+        divider.set_cursor_from_name(Some("col-resize"));
+        divider.append(&gtk::Separator::builder()
+            .orientation(Orientation::Vertical)
+            .halign(Align::Center)
+            .vexpand(true)
+            .build()
+        );
+        let divider_knob = gtk::Image::builder()
+            .icon_name("object-flip-horizontal-symbolic")
+            .pixel_size(16)
+            .build();
+        divider_knob.add_css_class("osd");
+        divider.append(&divider_knob);
+        divider.append(&gtk::Separator::builder()
+            .orientation(Orientation::Vertical)
+            .halign(Align::Center)
+            .vexpand(true)
+            .build()
+        );
+        preview_widget.add_overlay(&divider);
+
+        PreviewComparison {
+            compare_button: compare_button.clone(),
+            original_preview: original_preview.clone(),
+            original_clip,
+            divider,
+            preview_widget: Rc::clone(preview_widget),
+            image_preview: Rc::clone(image_preview),
+            split: Rc::new(Cell::new(0.5)),
+            held: Rc::new(Cell::new(false)),
+        }
+    }
+
+    // where the preview overlay places the original or the divider, None for its other children
+    pub fn child_rectangle(&self, overlay_child: &gtk::Widget) -> Option<Rectangle> {
+        let [display_width, display_height] = [self.image_preview.width(), self.image_preview.height()];
+        let split_x = match self.held.get() {
+            true => display_width,
+            false => (self.split.get() * display_width as f64).round() as i32,
+        };
+
+        if overlay_child == self.original_clip.upcast_ref::<gtk::Widget>() {
+            // the picture keeps the full size so the clip only cuts it off
+            if self.original_preview.width_request() != display_width || self.original_preview.height_request() != display_height {
+                self.original_preview.set_size_request(display_width, display_height);
+            }
+            return Some(Rectangle::new(0, 0, split_x, display_height));
+        }
+        if overlay_child == self.divider.upcast_ref::<gtk::Widget>() {
+            return Some(Rectangle::new(split_x - COMPARISON_DIVIDER_WIDTH / 2, 0, COMPARISON_DIVIDER_WIDTH, display_height));
+        }
+        None
+    }
+
+    // the original is shown while comparing, over the whole image while it's held
+    fn update(&self) {
+        let comparing = self.compare_button.is_active();
+        self.original_clip.set_visible(comparing || self.held.get());
+        self.divider.set_visible(comparing && !self.held.get());
+        self.preview_widget.queue_allocate();
+    }
+
+    fn hold(&self, held: bool) {
+        if self.held.replace(held) != held {
+            self.update();
+        }
+    }
+
+    // the compare button, holding `show_original_button` or backslash in `main_window` and dragging the divider
+    pub fn connect(&self, show_original_button: &Button, main_window: &ApplicationWindow) {
+        self.compare_button.connect_toggled({
+            let comparison = self.clone();
+            move |_| comparison.update()
+        });
+
+        // claimed before the button's own click, so it only reports the press and the release
+        let show_original_gesture = GestureClick::new();
+        show_original_gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+        show_original_gesture.connect_pressed({
+            let comparison = self.clone();
+            move |show_original_gesture, _, _, _| {
+                show_original_gesture.set_state(gtk::EventSequenceState::Claimed);
+                comparison.hold(true);
+            }
+        });
+        show_original_gesture.connect_released({
+            let comparison = self.clone();
+            move |_, _, _, _| comparison.hold(false)
+        });
+        show_original_gesture.connect_stopped({
+            let comparison = self.clone();
+            move |_| comparison.hold(false)
+        });
+        show_original_button.add_controller(show_original_gesture);
+
+        // holding backslash does the same, unless it's typed into a text field
+        let show_original_key_controller = EventControllerKey::new();
+        show_original_key_controller.connect_key_pressed({
+            let comparison = self.clone();
+            let main_window = main_window.clone();
+            move |_, key, _, _| {
+                let typing = GtkWindowExt::focus(&main_window).is_some_and(|focus| focus.type_().is_a(gtk::Editable::static_type()));
+                if key != Key::backslash || typing {
+                    return glib::Propagation::Proceed;
+                }
+                comparison.hold(true);
+                glib::Propagation::Stop
+            }
+        });
+        show_original_key_controller.connect_key_released({
+            let comparison = self.clone();
+            move |_, key, _, _| {
+                if key == Key::backslash {
+                    comparison.hold(false);
+                }
+            }
+        });
+        main_window.add_controller(show_original_key_controller);
+
+        // dragging the divider moves the split, claimed so the zoomed preview doesn't pan along
+        let split_start = Rc::new(Cell::new(0.5));
+        let divider_gesture = GestureDrag::new();
+        divider_gesture.connect_drag_begin({
+            let split_start = Rc::clone(&split_start);
+            let split = Rc::clone(&self.split);
+            move |divider_gesture, _, _| {
+                divider_gesture.set_state(gtk::EventSequenceState::Claimed);
+                split_start.set(split.get());
+            }
+        });
+        divider_gesture.connect_drag_update({
+            let comparison = self.clone();
+            let split_start = Rc::clone(&split_start);
+            move |_, dx, _| {
+                let display_width = comparison.image_preview.width().max(1) as f64;
+                comparison.split.set((split_start.get() + dx / display_width).clamp(0.0, 1.0));
+                comparison.preview_widget.queue_allocate();
+            }
+        });
+        self.divider.add_controller(divider_gesture);
+    }
+}
+
+// moving the watermark by dragging it, snapped to guides, and resizing it by the handles at its corners or by pinching,
+// cloning only clones the references
+#[derive(Clone)]
+pub struct WatermarkDragging {
+    // in the order top left, top right, bottom left, bottom right
+    handles:                Vec<Box>,
+    // lines of the guides a dragged watermark snapped to, vertical and horizontal
+    snap_guide_lines:       [Box; 2],
+    snap_guides:            Rc<Cell<[Option<f64>; 2]>>,
+    preview_widget:         Rc<Overlay>,
+    image_preview:          Rc<Picture>,
+    image_dimensions:       Rc<RefCell<[i32; 2]>>,
+    position_rows:          Rc<PositionRows>,
+    scale_slider:           Rc<Scale>,
+    margin_spin_row:        Rc<SpinRow>,
+    // the watermark's box in preview pixels, as the overlay places it
+    watermark_rectangle:    Rc<dyn Fn() -> Rectangle>,
+}
+
+impl WatermarkDragging {
+    // adds the handles and the guide lines as overlays of `preview_widget`
+    pub fn new(
+        preview_widget:         &Rc<Overlay>,
+        image_preview:          &Rc<Picture>,
+        image_dimensions:       &Rc<RefCell<[i32; 2]>>,
+        position_rows:          &Rc<PositionRows>,
+        scale_slider:           &Rc<Scale>,
+        margin_spin_row:        &Rc<SpinRow>,
+        watermark_rectangle:    Rc<dyn Fn() -> Rectangle>,
+    ) -> WatermarkDragging {
+        let handles = ["nwse-resize", "nesw-resize", "nesw-resize", "nwse-resize"]
+            .into_iter()
+            .map(|cursor_name| {
+                let handle = Box::builder()
+                    .width_request(WATERMARK_HANDLE_SIZE)
+                    .height_request(WATERMARK_HANDLE_SIZE)
+                    .build();
+                handle.add_css_class("watermark-handle");
+                handle.set_cursor_from_name(Some(cursor_name));
+                preview_widget.add_overlay(&handle);
+                handle
+            })
+            .collect();
+
+        let snap_guide_lines = [Orientation::Vertical, Orientation::Horizontal].map(|orientation| {
+            let snap_guide_line = Box::builder()
+                .orientation(orientation)
+                .can_target(false)
+                .visible(false)
+                .build();
+            snap_guide_line.add_css_class("snap-guide");
+            preview_widget.add_overlay(&snap_guide_line);
+            snap_guide_line
+        });
+
+        WatermarkDragging {
+            handles,
+            snap_guide_lines,
+            snap_guides: Rc::new(Cell::new([None, None])),
+            preview_widget: Rc::clone(preview_widget),
+            image_preview: Rc::clone(image_preview),
+            image_dimensions: Rc::clone(image_dimensions),
+            position_rows: Rc::clone(position_rows),
+            scale_slider: Rc::clone(scale_slider),
+            margin_spin_row: Rc::clone(margin_spin_row),
+            watermark_rectangle,
+        }
+    }
+
+    // where the preview overlay places a guide line or a handle, None for its other children
+    pub fn child_rectangle(&self, overlay_child: &gtk::Widget) -> Option<Rectangle> {
+        let [display_width, display_height] = [self.image_preview.width(), self.image_preview.height()];
+        let [vertical_guide, horizontal_guide] = self.snap_guides.get();
+        if overlay_child == self.snap_guide_lines[0].upcast_ref::<gtk::Widget>() {
+            let x = (vertical_guide.unwrap_or_default().round() as i32).clamp(1, display_width.max(1)) - 1;
+            return Some(Rectangle::new(x, 0, 2, display_height));
+        }
+        if overlay_child == self.snap_guide_lines[1].upcast_ref::<gtk::Widget>() {
+            let y = (horizontal_guide.unwrap_or_default().round() as i32).clamp(1, display_height.max(1)) - 1;
+            return Some(Rectangle::new(0, y, display_width, 2));
+        }
+
+        let corner = self.handles.iter().position(|handle| overlay_child == handle.upcast_ref::<gtk::Widget>())?;
+        let watermark_rectangle = (self.watermark_rectangle)();
+        let x = watermark_rectangle.x() + watermark_rectangle.width() * (corner as i32 % 2);
+        let y = watermark_rectangle.y() + watermark_rectangle.height() * (corner as i32 / 2);
+        Some(Rectangle::new(x - WATERMARK_HANDLE_SIZE / 2, y - WATERMARK_HANDLE_SIZE / 2, WATERMARK_HANDLE_SIZE, WATERMARK_HANDLE_SIZE))
+    }
+
+    pub fn set_handles_visible(&self, visible: bool) {
+        for handle in &self.handles {
+            handle.set_visible(visible);
+        }
+    }
+
+    // moves the watermark's box to `position` in preview pixels, anchored to the third of the image its centre is in
+    // so the offset stays small and the export places it proportionally on images of other sizes
+    fn place(&self, [x, y]: [f64; 2]) {
+        let [image_width, image_height] = *self.image_dimensions.borrow();
+        let [display_width, display_height] = [self.image_preview.width(), self.image_preview.height()];
+        if image_width <= 0 || image_height <= 0 || display_width <= 0 || display_height <= 0 {
+            return;
+        }
+
+        let current_rectangle = (self.watermark_rectangle)();
+        let [width, height] = [current_rectangle.width(), current_rectangle.height()];
+        let third = |center: f64, length: i32| ((center / length as f64 * 3.0).floor() as i64).clamp(0, 2) as u32;
+        let anchor = Anchor::from_index(
+            third(y + height as f64 / 2.0, display_height) * 3 + third(x + width as f64 / 2.0, display_width),
+        );
+
+        // the margin is scaled to the preview like `preview_watermark_rectangle` does
+        let margin = (self.margin_spin_row.value() as f32 * display_width as f32 / image_width as f32).ceil() as i64;
+        let [anchored_x, anchored_y] = anchor.position(
+            [display_width as i64, display_height as i64],
+            [width as i64, height as i64],
+            margin,
+            [0, 0],
+        );
+        let relative_offset = [
+            (x - anchored_x as f64) / display_width as f64,
+            (y - anchored_y as f64) / display_height as f64,
+        ];
+
+        let position_rows = &self.position_rows;
+        position_rows.anchor_buttons[anchor.index() as usize].set_active(true);
+        let offset = match position_rows.offset_unit_toggle_group.active() {
+            1 => relative_offset.map(|relative_offset| relative_offset * 100.0),
+            _ => [relative_offset[0] * image_width as f64, relative_offset[1] * image_height as f64],
+        };
+        position_rows.offset_x_spin_row.set_value(offset[0]);
+        position_rows.offset_y_spin_row.set_value(offset[1]);
+    }
+
+    // scales the watermark by `factor` of `start_scale`, keeping the point at `fixed` of its box in place
+    fn resize(&self, start_scale: f64, factor: f64, start_rectangle: Rectangle, fixed: [f64; 2]) {
+        // not rounded to the slider's digits, the size follows the pointer smoothly
+        self.scale_slider.adjustment().set_value(start_scale * factor);
+
+        let new_rectangle = (self.watermark_rectangle)();
+        let fixed_point = [
+            start_rectangle.x() as f64 + start_rectangle.width() as f64 * fixed[0],
+            start_rectangle.y() as f64 + start_rectangle.height() as f64 * fixed[1],
+        ];
+        self.place([
+            fixed_point[0] - new_rectangle.width() as f64 * fixed[0],
+            fixed_point[1] - new_rectangle.height() as f64 * fixed[1],
+        ]);
+    }
+
+    fn show_snap_guides(&self, guides: [Option<f64>; 2]) {
+        self.snap_guides.set(guides);
+        for (snap_guide_line, guide) in self.snap_guide_lines.iter().zip(guides) {
+            snap_guide_line.set_visible(guide.is_some());
+        }
+        self.preview_widget.queue_allocate();
+    }
+
+    // dragging and pinching `watermark_preview` and dragging the handles, all claimed so the zoomed preview doesn't
+    // pan along
+    pub fn connect(&self, watermark_preview: &Picture) {
+        watermark_preview.set_cursor_from_name(Some("move"));
+
+        let drag_start_rectangle = Rc::new(Cell::new(Rectangle::new(0, 0, 0, 0)));
+        let move_gesture = GestureDrag::new();
+        move_gesture.connect_drag_begin({
+            let dragging = self.clone();
+            let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+            move |move_gesture, _, _| {
+                move_gesture.set_state(gtk::EventSequenceState::Claimed);
+                drag_start_rectangle.set((dragging.watermark_rectangle)());
+            }
+        });
+        move_gesture.connect_drag_update({
+            let dragging = self.clone();
+            let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+            move |_, dx, dy| {
+                let start_rectangle = drag_start_rectangle.get();
+                let [display_width, display_height] = [dragging.image_preview.width() as f64, dragging.image_preview.height() as f64];
+                let (x, vertical_guide) = snap_to_guides(start_rectangle.x() as f64 + dx, start_rectangle.width() as f64, display_width);
+                let (y, horizontal_guide) = snap_to_guides(start_rectangle.y() as f64 + dy, start_rectangle.height() as f64, display_height);
+                dragging.place([x, y]);
+                dragging.show_snap_guides([vertical_guide, horizontal_guide]);
+            }
+        });
+        move_gesture.connect_drag_end({
+            let dragging = self.clone();
+            move |_, _, _| dragging.show_snap_guides([None, None])
+        });
+        watermark_preview.add_controller(move_gesture);
+
+        let resize_start_scale = Rc::new(Cell::new(0.0));
+        for (corner, handle) in self.handles.iter().enumerate() {
+            // the dragged corner moves away from the opposite one, which stays in place
+            let direction = [if corner % 2 == 0 { -1.0 } else { 1.0 }, if corner / 2 == 0 { -1.0 } else { 1.0 }];
+            let fixed = direction.map(|direction| if direction < 0.0 { 1.0 } else { 0.0 });
+
+            let resize_gesture = GestureDrag::new();
+            resize_gesture.connect_drag_begin({
+                let dragging = self.clone();
+                let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+                let resize_start_scale = Rc::clone(&resize_start_scale);
+                move |resize_gesture, _, _| {
+                    resize_gesture.set_state(gtk::EventSequenceState::Claimed);
+                    drag_start_rectangle.set((dragging.watermark_rectangle)());
+                    resize_start_scale.set(dragging.scale_slider.value());
+                }
+            });
+            resize_gesture.connect_drag_update({
+                let dragging = self.clone();
+                let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+                let resize_start_scale = Rc::clone(&resize_start_scale);
+                move |_, dx, dy| {
+                    let start_rectangle = drag_start_rectangle.get();
+                    let [start_width, start_height] = [start_rectangle.width().max(1) as f64, start_rectangle.height().max(1) as f64];
+                    // the box keeps its aspect ratio, the pointer's larger pull decides the size
+                    let factor = ((start_width + direction[0] * dx) / start_width).max((start_height + direction[1] * dy) / start_height);
+                    dragging.resize(resize_start_scale.get(), factor.max(0.0), start_rectangle, fixed);
+                }
+            });
+            handle.add_controller(resize_gesture);
+        }
+
+        // pinching the watermark scales it around its centre
+        let pinch_gesture = GestureZoom::new();
+        pinch_gesture.connect_begin({
+            let dragging = self.clone();
+            let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+            let resize_start_scale = Rc::clone(&resize_start_scale);
+            move |pinch_gesture, _| {
+                pinch_gesture.set_state(gtk::EventSequenceState::Claimed);
+                drag_start_rectangle.set((dragging.watermark_rectangle)());
+                resize_start_scale.set(dragging.scale_slider.value());
+            }
+        });
+        pinch_gesture.connect_scale_changed({
+            let dragging = self.clone();
+            let drag_start_rectangle = Rc::clone(&drag_start_rectangle);
+            let resize_start_scale = Rc::clone(&resize_start_scale);
+            move |_, scale| dragging.resize(resize_start_scale.get(), scale, drag_start_rectangle.get(), [0.5, 0.5])
+        });
+        watermark_preview.add_controller(pinch_gesture);
+    }
+}